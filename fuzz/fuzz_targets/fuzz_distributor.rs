@@ -0,0 +1,175 @@
+//! Sequences random calls to the distributor's entrypoints, with random auths and ledger jumps,
+//! against the native contract. Checks that tokens are conserved, that no user is paid twice,
+//! and that only `ContractError` codes (or auth failures when auths are not mocked) surface.
+//! Calls before a successful `initialize` are skipped, as the contract has no state to read.
+//!
+//! Run with `cargo +nightly-2024-06-01 fuzz run fuzz_distributor` from the repository root, using
+//! the nightly pinned in `fuzz/rust-toolchain.toml`.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use basic_distributor::{
+    testutils::EnvTestUtils, ContractError, Distributor, DistributorClient, DistributorOption,
+};
+use libfuzzer_sys::fuzz_target;
+use soroban_sdk::{
+    testutils::Address as _,
+    token::{StellarAssetClient, TokenClient},
+    vec,
+    xdr::{ScErrorCode, ScErrorType},
+    Address, Env, Error, InvokeError, Vec,
+};
+
+const ONE_DAY_LEDGERS: u32 = 17280;
+const USER_COUNT: usize = 4;
+const MAX_JUMP_LEDGERS: u32 = 30 * ONE_DAY_LEDGERS;
+// keep the timeline within the minimum persistent TTL the contract instances and balances are
+// created with, as nothing else extends them while the ledger jumps
+const MAX_TIMELINE_LEDGERS: u32 = 100 * ONE_DAY_LEDGERS;
+
+#[derive(Arbitrary, Debug)]
+enum Call {
+    Initialize {
+        deadline_days: u8,
+        timelock: u16,
+        fee_bps: Option<u16>,
+    },
+    SetDistribution {
+        distributions: std::vec::Vec<(u8, u8, i64)>,
+    },
+    Finalize,
+    SetAdmin {
+        user: u8,
+    },
+    Claim {
+        user: u8,
+    },
+    Refund,
+    Jump {
+        ledgers: u32,
+    },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Step {
+    call: Call,
+    /// Mock all auths for the call, otherwise no auths are provided
+    mock_auths: bool,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    funding: u32,
+    steps: std::vec::Vec<Step>,
+}
+
+/// Check that a failed call surfaced a documented error
+fn check_error(error: Result<Error, InvokeError>, mock_auths: bool) {
+    let error = error.expect("call failed with an undocumented invoke error");
+    // a missing auth surfaces as an invalid action from the native contract's frame
+    let auth_error = error.is_type(ScErrorType::Auth)
+        || error == Error::from_type_and_code(ScErrorType::Context, ScErrorCode::InvalidAction);
+    if !mock_auths && auth_error {
+        return;
+    }
+    assert!(
+        error.is_type(ScErrorType::Contract) && ContractError::try_from(error).is_ok(),
+        "call failed with an undocumented error: {:?}",
+        error
+    );
+}
+
+fuzz_target!(|input: Input| {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_client = DistributorClient::new(&env, &env.register_contract(None, Distributor));
+    let token = env.register_stellar_asset_contract(Address::generate(&env));
+    let token_client = TokenClient::new(&env, &token);
+    let fee_recipient = Address::generate(&env);
+    let users: std::vec::Vec<Address> = (0..USER_COUNT).map(|_| Address::generate(&env)).collect();
+    let funding = input.funding as i128;
+    StellarAssetClient::new(&env, &token).mint(&dist_client.address, &funding);
+
+    let end = env.ledger().sequence() + MAX_TIMELINE_LEDGERS;
+    let mut initialized = false;
+    let mut paid = [false; USER_COUNT];
+    for step in input.steps {
+        if !initialized && !matches!(step.call, Call::Initialize { .. }) {
+            continue;
+        }
+        if step.mock_auths {
+            env.mock_all_auths();
+        } else {
+            env.set_auths(&[]);
+        }
+
+        let result = match step.call {
+            Call::Initialize {
+                deadline_days,
+                timelock,
+                fee_bps,
+            } => {
+                let mut options = vec![&env, DistributorOption::Timelock(timelock as u32)];
+                if let Some(rate_bps) = fee_bps {
+                    options.push_back(DistributorOption::Fee(basic_distributor::ClaimFee {
+                        rate_bps: rate_bps as u32,
+                        recipient: fee_recipient.clone(),
+                    }));
+                }
+                let result = dist_client
+                    .try_initialize(
+                        &vec![&env, token.clone()],
+                        &(env.ledger().sequence() + deadline_days as u32 * ONE_DAY_LEDGERS),
+                        &users[0],
+                        &options,
+                    )
+                    .map(|_| ());
+                initialized |= result.is_ok();
+                result
+            }
+            Call::SetDistribution { distributions } => {
+                let mut batch = Vec::new(&env);
+                for (user, token_index, amount) in distributions {
+                    batch.push_back((
+                        users[user as usize % USER_COUNT].clone(),
+                        vec![&env, (token_index as u32 % 2, amount as i128)],
+                    ));
+                }
+                dist_client.try_set_distribution(&batch).map(|_| ())
+            }
+            Call::Finalize => dist_client.try_finalize(&None, &None).map(|_| ()),
+            Call::SetAdmin { user } => dist_client
+                .try_set_admin(&users[user as usize % USER_COUNT])
+                .map(|_| ()),
+            Call::Claim { user } => {
+                let index = user as usize % USER_COUNT;
+                let result = dist_client.try_claim(&users[index]);
+                if result.is_ok() {
+                    assert!(!paid[index], "user was paid twice");
+                    paid[index] = true;
+                }
+                result.map(|_| ())
+            }
+            Call::Refund => dist_client.try_refund().map(|_| ()),
+            Call::Jump { ledgers } => {
+                env.jump((ledgers % MAX_JUMP_LEDGERS).min(end - env.ledger().sequence()));
+                Ok(())
+            }
+        };
+        if let Err(error) = result {
+            check_error(error, step.mock_auths);
+        }
+
+        // tokens are only moved between the distributor, the users and the fee recipient
+        let mut total =
+            token_client.balance(&dist_client.address) + token_client.balance(&fee_recipient);
+        for user in &users {
+            total += token_client.balance(user);
+        }
+        assert_eq!(total, funding, "tokens were not conserved");
+    }
+});
@@ -0,0 +1,42 @@
+//! Off-chain computation of the allocation hash reported by `get_allocation_hash`.
+//!
+//! Each allocation written by `set_distribution` or `revoke` rolls the hash forward as
+//! `sha256(hash || xdr(ScVal::Address(user)) || amount_0 || .. || amount_n)`, with one 16 byte
+//! big-endian amount per token, starting from 32 zero bytes.
+
+use std::vec::Vec;
+
+use sha2::{Digest, Sha256};
+use soroban_sdk::xdr::{Limits, ScAddress, ScVal, WriteXdr};
+
+use crate::merkle::Hash;
+
+/// The allocation hash of a newly initialized contract
+pub const EMPTY_HASH: Hash = [0; 32];
+
+/// Roll the allocation hash forward with a user's allocation of every token
+pub fn roll_hash(hash: &Hash, user: &ScAddress, amounts: &[i128]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update(hash);
+    hasher.update(
+        ScVal::Address(user.clone())
+            .to_xdr(Limits::none())
+            .expect("address encodes to xdr"),
+    );
+    for amount in amounts {
+        hasher.update(amount.to_be_bytes());
+    }
+    hasher.finalize().into()
+}
+
+/// Compute the allocation hash the contract reports once every allocation has been written in
+/// order on a newly initialized contract
+pub fn allocation_hash<'a>(
+    allocations: impl IntoIterator<Item = &'a (ScAddress, Vec<i128>)>,
+) -> Hash {
+    allocations
+        .into_iter()
+        .fold(EMPTY_HASH, |hash, (user, amounts)| {
+            roll_hash(&hash, user, amounts)
+        })
+}
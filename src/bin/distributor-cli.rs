@@ -0,0 +1,429 @@
+//! Turn a CSV of `address,amount` rows into batches of `set_distribution` arguments.
+//!
+//! ```text
+//! distributor-cli <csv> [--format json|xdr|merkle] [--batch-size N] [--tokens N] [--token-index N]
+//!                       [--merge-duplicates]
+//! ```
+//!
+//! Each batch stays under the Soroban ledger entry limits for a single `set_distribution` call.
+//! The `json` format (default) prints the totals, the allocation hash the contract will report
+//! once every batch is uploaded in order, and each batch with its base64 XDR argument. The `xdr`
+//! format prints only the base64 XDR argument of each batch, one per line. The `merkle` format
+//! instead prints the root, totals and each user's proof of a Merkle tree of the allocations,
+//! for use with `set_merkle_root` and `claim_merkle`.
+
+use std::{collections::HashMap, fmt::Write as _, fs::File, io::Read, process::ExitCode};
+
+use basic_distributor::{allocation::allocation_hash, merkle::MerkleTree};
+use serde_json::json;
+use stellar_xdr::curr::{Int128Parts, Limits, ScAddress, ScVal, ScVec, WriteXdr};
+
+/// The maximum ledger entries a transaction can access, including the entries it writes
+const MAX_READ_ENTRIES: usize = 40;
+/// The maximum ledger entries a transaction can write
+const MAX_WRITE_ENTRIES: usize = 25;
+/// The ledger entries accessed by every `set_distribution` call: the contract instance and code,
+/// the admin account, and up to two recipient pages
+const FIXED_ENTRIES: usize = 5;
+/// The ledger entries written by every `set_distribution` call: the contract instance and up to
+/// two recipient pages
+const FIXED_WRITE_ENTRIES: usize = 3;
+/// The ledger entries accessed for each recipient: their distribution and denylist entries
+const ENTRIES_PER_RECIPIENT: usize = 2;
+/// The largest batch that stays under the ledger entry limits. Each recipient's distribution
+/// is written, so the batch is also bound by the write limit.
+///
+/// Only entry counts are checked. The `cost_estimate` measurements show a batch of this size
+/// using under a third of the instruction limit and well under the read and write byte limits,
+/// so the entry limits are the ones reached first. Re-run them if the stored data grows.
+const MAX_BATCH_SIZE: usize = {
+    let read_bound = (MAX_READ_ENTRIES - FIXED_ENTRIES) / ENTRIES_PER_RECIPIENT;
+    let write_bound = MAX_WRITE_ENTRIES - FIXED_WRITE_ENTRIES;
+    if read_bound < write_bound {
+        read_bound
+    } else {
+        write_bound
+    }
+};
+
+#[derive(Debug, PartialEq)]
+enum Format {
+    Json,
+    Xdr,
+    Merkle,
+}
+
+#[derive(Debug)]
+struct Options {
+    path: String,
+    format: Format,
+    batch_size: usize,
+    tokens: u32,
+    token_index: u32,
+    merge_duplicates: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Allocation {
+    address: ScAddress,
+    amount: i128,
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let options = parse_args(std::env::args().skip(1))?;
+    let file = File::open(&options.path).map_err(|err| format!("{}: {}", options.path, err))?;
+    let allocations = read_allocations(file, options.merge_duplicates)?;
+
+    match options.format {
+        Format::Merkle => {
+            let tree = MerkleTree::new(token_amounts(
+                &allocations,
+                options.tokens,
+                options.token_index,
+            ))
+            .map_err(|err| format!("failed to build merkle tree: {:?}", err))?;
+            println!("{}", serde_json::to_string_pretty(&tree.to_json()).unwrap());
+        }
+        Format::Xdr => {
+            for (_, xdr) in encode_batches(&allocations, options.batch_size, options.token_index)? {
+                println!("{}", xdr);
+            }
+        }
+        Format::Json => {
+            let batches = encode_batches(&allocations, options.batch_size, options.token_index)?;
+            let total = total_amount(&allocations)?;
+            let hash = allocation_hash(&token_amounts(
+                &allocations,
+                options.tokens,
+                options.token_index,
+            ));
+            let batches = batches
+                .iter()
+                .map(|(batch, xdr)| {
+                    Ok(json!({
+                        "recipients": batch.len(),
+                        "total": total_amount(batch)?.to_string(),
+                        "distributions": batch
+                            .iter()
+                            .map(|allocation| json!([
+                                allocation.address.to_string(),
+                                [[options.token_index, allocation.amount.to_string()]]
+                            ]))
+                            .collect::<Vec<_>>(),
+                        "xdr": xdr,
+                    }))
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            let output = json!({
+                "recipients": allocations.len(),
+                "total": total.to_string(),
+                "allocation_hash": to_hex(&hash),
+                "batches": batches,
+            });
+            println!("{}", serde_json::to_string_pretty(&output).unwrap());
+        }
+    }
+    Ok(())
+}
+
+/// Parse the command line arguments, excluding the program name
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut path = None;
+    let mut options = Options {
+        path: String::new(),
+        format: Format::Json,
+        batch_size: MAX_BATCH_SIZE,
+        tokens: 1,
+        token_index: 0,
+        merge_duplicates: false,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("{} requires a value", name))
+        };
+        match arg.as_str() {
+            "--format" => {
+                options.format = match value("--format")?.as_str() {
+                    "json" => Format::Json,
+                    "xdr" => Format::Xdr,
+                    "merkle" => Format::Merkle,
+                    other => return Err(format!("unknown format: {}", other)),
+                }
+            }
+            "--batch-size" => options.batch_size = parse_number(&value("--batch-size")?)?,
+            "--tokens" => options.tokens = parse_number(&value("--tokens")?)?,
+            "--token-index" => options.token_index = parse_number(&value("--token-index")?)?,
+            "--merge-duplicates" => options.merge_duplicates = true,
+            _ if arg.starts_with("--") => return Err(format!("unknown option: {}", arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(format!("unexpected argument: {}", arg)),
+        }
+    }
+
+    options.path = path.ok_or("usage: distributor-cli <csv> [options]")?;
+    if options.batch_size == 0 || options.batch_size > MAX_BATCH_SIZE {
+        return Err(format!(
+            "--batch-size must be between 1 and {}",
+            MAX_BATCH_SIZE
+        ));
+    }
+    if options.token_index >= options.tokens {
+        return Err("--token-index must be less than --tokens".into());
+    }
+    Ok(options)
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid number: {}", value))
+}
+
+/// Read the allocations from a CSV of `address,amount` rows, with an optional header row.
+///
+/// Rows repeating an earlier address and amount are dropped. Rows repeating an earlier address
+/// with a different amount are an error, unless `merge_duplicates` is set, in which case the
+/// amounts are summed. Allocations are returned in the order each address first appears.
+fn read_allocations(reader: impl Read, merge_duplicates: bool) -> Result<Vec<Allocation>, String> {
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let mut allocations: Vec<Allocation> = Vec::new();
+    let mut indexes: HashMap<ScAddress, usize> = HashMap::new();
+    for (index, record) in csv_reader.records().enumerate() {
+        let line = index + 1;
+        let record = record.map_err(|err| format!("line {}: {}", line, err))?;
+        if record.len() != 2 {
+            return Err(format!("line {}: expected 2 columns", line));
+        }
+        if line == 1 && record[0].eq_ignore_ascii_case("address") {
+            continue;
+        }
+
+        let address: ScAddress = record[0]
+            .parse()
+            .map_err(|_| format!("line {}: invalid address {}", line, &record[0]))?;
+        let amount: i128 = record[1]
+            .parse()
+            .map_err(|_| format!("line {}: invalid amount {}", line, &record[1]))?;
+        if amount <= 0 {
+            return Err(format!("line {}: amount must be positive", line));
+        }
+
+        match indexes.get(&address) {
+            Some(&existing) if merge_duplicates => {
+                let allocation = &mut allocations[existing];
+                allocation.amount = allocation
+                    .amount
+                    .checked_add(amount)
+                    .ok_or_else(|| format!("line {}: amount overflow", line))?;
+            }
+            Some(&existing) if allocations[existing].amount == amount => {}
+            Some(_) => {
+                return Err(format!(
+                    "line {}: {} has conflicting amounts",
+                    line, &record[0]
+                ))
+            }
+            None => {
+                indexes.insert(address.clone(), allocations.len());
+                allocations.push(Allocation { address, amount });
+            }
+        }
+    }
+    Ok(allocations)
+}
+
+/// Sum the amounts of the allocations
+fn total_amount(allocations: &[Allocation]) -> Result<i128, String> {
+    allocations.iter().try_fold(0_i128, |total, allocation| {
+        total
+            .checked_add(allocation.amount)
+            .ok_or_else(|| "total amount overflow".to_string())
+    })
+}
+
+fn i128_val(amount: i128) -> ScVal {
+    ScVal::I128(Int128Parts {
+        hi: (amount >> 64) as i64,
+        lo: amount as u64,
+    })
+}
+
+fn vec_val(values: Vec<ScVal>) -> Result<ScVal, String> {
+    let vec = ScVec::try_from(values).map_err(|err| format!("batch too large: {}", err))?;
+    Ok(ScVal::Vec(Some(vec)))
+}
+
+/// Build the `distributions` argument of `set_distribution` for a batch
+fn batch_arg(batch: &[Allocation], token_index: u32) -> Result<ScVal, String> {
+    let distributions = batch
+        .iter()
+        .map(|allocation| {
+            let amounts = vec_val(vec![vec_val(vec![
+                ScVal::U32(token_index),
+                i128_val(allocation.amount),
+            ])?])?;
+            vec_val(vec![ScVal::Address(allocation.address.clone()), amounts])
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    vec_val(distributions)
+}
+
+/// Split the allocations into batches, each with its base64 XDR `set_distribution` argument
+fn encode_batches(
+    allocations: &[Allocation],
+    batch_size: usize,
+    token_index: u32,
+) -> Result<Vec<(&[Allocation], String)>, String> {
+    allocations
+        .chunks(batch_size)
+        .map(|batch| {
+            let arg = batch_arg(batch, token_index)?;
+            arg.to_xdr_base64(Limits::none())
+                .map_err(|err| format!("failed to encode batch: {}", err))
+                .map(|xdr| (batch, xdr))
+        })
+        .collect()
+}
+
+/// Expand each allocation into its amount of every token, as the contract stores it
+fn token_amounts(
+    allocations: &[Allocation],
+    tokens: u32,
+    token_index: u32,
+) -> Vec<(ScAddress, Vec<i128>)> {
+    allocations
+        .iter()
+        .map(|allocation| {
+            let mut amounts = vec![0; tokens as usize];
+            amounts[token_index as usize] = allocation.amount;
+            (allocation.address.clone(), amounts)
+        })
+        .collect()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDR_1: &str = "GAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAWHF";
+    const ADDR_2: &str = "CAAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQCAIBAEAQC526";
+
+    #[test]
+    fn test_read_allocations() {
+        let csv = format!(
+            "address,amount\n{},100\n {} , 200 \n{},100\n",
+            ADDR_1, ADDR_2, ADDR_1
+        );
+        let allocations = read_allocations(csv.as_bytes(), false).unwrap();
+        assert_eq!(
+            allocations,
+            vec![
+                Allocation {
+                    address: ADDR_1.parse().unwrap(),
+                    amount: 100
+                },
+                Allocation {
+                    address: ADDR_2.parse().unwrap(),
+                    amount: 200
+                },
+            ]
+        );
+
+        // conflicting duplicates are rejected unless merged
+        let csv = format!("{},100\n{},50\n", ADDR_1, ADDR_1);
+        assert_eq!(
+            read_allocations(csv.as_bytes(), false).err(),
+            Some(format!("line 2: {} has conflicting amounts", ADDR_1))
+        );
+        let allocations = read_allocations(csv.as_bytes(), true).unwrap();
+        assert_eq!(allocations.len(), 1);
+        assert_eq!(allocations[0].amount, 150);
+
+        let csv = format!("{},0\n", ADDR_1);
+        assert_eq!(
+            read_allocations(csv.as_bytes(), false).err(),
+            Some("line 1: amount must be positive".to_string())
+        );
+        let csv = "GABC,100\n";
+        assert_eq!(
+            read_allocations(csv.as_bytes(), false).err(),
+            Some("line 1: invalid address GABC".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = |args: &[&str]| parse_args(args.iter().map(|arg| arg.to_string()));
+
+        let options = args(&["list.csv"]).unwrap();
+        assert_eq!(options.path, "list.csv");
+        assert_eq!(options.format, Format::Json);
+        assert_eq!(options.batch_size, MAX_BATCH_SIZE);
+
+        let options = args(&["--format", "xdr", "list.csv", "--batch-size", "5"]).unwrap();
+        assert_eq!(options.format, Format::Xdr);
+        assert_eq!(options.batch_size, 5);
+
+        assert!(args(&["list.csv", "--batch-size", "100"]).is_err());
+        assert!(args(&["list.csv", "--tokens", "2", "--token-index", "2"]).is_err());
+        assert!(args(&[]).is_err());
+    }
+
+    #[test]
+    fn test_batch_arg() {
+        let allocation = Allocation {
+            address: ADDR_1.parse().unwrap(),
+            amount: -2,
+        };
+        let arg = batch_arg(std::slice::from_ref(&allocation), 1).unwrap();
+        let expected = ScVal::Vec(Some(
+            vec![ScVal::Vec(Some(
+                vec![
+                    ScVal::Address(allocation.address),
+                    ScVal::Vec(Some(
+                        vec![ScVal::Vec(Some(
+                            vec![
+                                ScVal::U32(1),
+                                ScVal::I128(Int128Parts {
+                                    hi: -1,
+                                    lo: u64::MAX - 1,
+                                }),
+                            ]
+                            .try_into()
+                            .unwrap(),
+                        ))]
+                        .try_into()
+                        .unwrap(),
+                    )),
+                ]
+                .try_into()
+                .unwrap(),
+            ))]
+            .try_into()
+            .unwrap(),
+        ));
+        assert_eq!(arg, expected);
+    }
+}
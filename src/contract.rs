@@ -1,11 +1,17 @@
+use core::mem::discriminant;
+
 use soroban_sdk::{
-    assert_with_error, contract, contractimpl, token::TokenClient, Address, Env, Vec,
+    assert_with_error, contract, contractimpl, panic_with_error, token::TokenClient,
+    unwrap::UnwrapOptimized, xdr::ToXdr, Address, Bytes, BytesN, Env, Vec,
 };
 
 use crate::{
     errors::ContractError,
     events::ContractEvents,
-    storage::{self, ONE_DAY_LEDGERS},
+    receiver::DistributionReceiverClient,
+    registry::AllowlistRegistryClient,
+    storage::{self, ONE_DAY_LEDGERS, SCHEMA_VERSION},
+    types::{ClaimFee, DistributionStatus, DistributorOption, LockBonus, MerkleRoot, SharePool},
 };
 
 #[contract]
@@ -16,32 +22,90 @@ impl Distributor {
     /// Initialize the contract
     ///
     /// ### Arguments
-    /// * `token` - The token to distribute
+    /// * `tokens` - The tokens to distribute, referenced by their index in distributions
     /// * `deadline` - The deadline ledger sequence number of the distribution
     /// * `admin` - The admin of the contract
+    /// * `options` - The timelock, claim fee, lock bonus and allowlist registry to enable, if any
     ///
     /// ### Panics
     /// * `AlreadyInitializedError` - If the contract has already been initialized
-    /// * `DeadlineError` - If the deadline is not withing [30, 90] days of ledgers in the future
-    ///                     assuming 5s a ledger
-    pub fn initialize(e: Env, token: Address, deadline: u32, admin: Address) {
+    /// * `OptionError` - If an option is provided more than once
+    /// * `InvalidTokenError` - If no tokens are provided or a token is provided more than once
+    /// * `FeeError` - If the fee rate is greater than 10000 basis points
+    /// * `LockError` - If the lock bonus is below 10000 bps or releases before the deadline
+    /// * `DeadlineError` - If the deadline is not within [30, 90] days in the future
+    pub fn initialize(
+        e: Env,
+        tokens: Vec<Address>,
+        deadline: u32,
+        admin: Address,
+        options: Vec<DistributorOption>,
+    ) {
         assert_with_error!(
             &e,
-            !storage::get_is_init(&e),
+            storage::get_schema_version(&e) == 0,
             ContractError::AlreadyInitializedError
         );
+        assert_with_error!(&e, !tokens.is_empty(), ContractError::InvalidTokenError);
+        for (index, token) in tokens.iter().enumerate() {
+            assert_with_error!(
+                &e,
+                tokens.first_index_of(&token) == Some(index as u32),
+                ContractError::InvalidTokenError
+            );
+        }
         assert_with_error!(
             &e,
             deadline >= e.ledger().sequence() + 30 * ONE_DAY_LEDGERS
                 && deadline <= e.ledger().sequence() + 90 * ONE_DAY_LEDGERS,
             ContractError::DeadlineError
         );
+        let mut timelock = 0;
+        for (index, option) in options.iter().enumerate() {
+            assert_with_error!(
+                &e,
+                options
+                    .iter()
+                    .take(index)
+                    .all(|prev_option| discriminant(&prev_option) != discriminant(&option)),
+                ContractError::OptionError
+            );
+            match option {
+                DistributorOption::Timelock(ledgers) => timelock = ledgers,
+                DistributorOption::Fee(fee) => {
+                    assert_with_error!(&e, fee.rate_bps <= 10_000, ContractError::FeeError);
+                    storage::set_fee(&e, &fee);
+                }
+                DistributorOption::LockBonus(lock_bonus) => {
+                    assert_with_error!(
+                        &e,
+                        lock_bonus.multiplier_bps >= 10_000
+                            && lock_bonus.release_ledger >= deadline,
+                        ContractError::LockError
+                    );
+                    storage::set_lock_bonus(&e, &lock_bonus);
+                }
+                DistributorOption::Registry(registry) => storage::set_registry(&e, &registry),
+            }
+        }
 
-        storage::set_token(&e, &token);
+        let zero_amounts = zero_amounts(&e, tokens.len());
+        storage::set_tokens(&e, &tokens);
         storage::set_deadline(&e, &deadline);
         storage::set_admin(&e, &admin);
+        storage::set_timelock(&e, &timelock);
+        storage::set_status(
+            &e,
+            &DistributionStatus {
+                total_allocated: zero_amounts.clone(),
+                total_claimed: zero_amounts.clone(),
+                total_fees: zero_amounts.clone(),
+                total_locked: zero_amounts,
+                claim_count: 0,
+            },
+        );
 
-        storage::set_is_init(&e);
+        storage::set_schema_version(&e, &SCHEMA_VERSION);
     }
 
     //********** Read-Only ***********//
@@ -61,39 +125,262 @@ impl Distributor {
         storage::get_admin(&e)
     }
 
-    /// Fetch the token being distributed
-    pub fn get_token(e: Env) -> Address {
-        storage::get_token(&e)
+    /// Fetch the tokens being distributed
+    pub fn get_tokens(e: Env) -> Vec<Address> {
+        storage::get_tokens(&e)
+    }
+
+    /// Fetch the fee taken from each claim, if any
+    pub fn get_fee(e: Env) -> Option<ClaimFee> {
+        storage::get_fee(&e)
+    }
+
+    /// Fetch the bonus for users who lock their distribution, if any
+    pub fn get_lock_bonus(e: Env) -> Option<LockBonus> {
+        storage::get_lock_bonus(&e)
+    }
+
+    /// Fetch the Merkle root of allocations claimable with `claim_merkle`, if any
+    pub fn get_merkle_root(e: Env) -> Option<MerkleRoot> {
+        storage::get_merkle_root(&e)
+    }
+
+    /// Fetch the share pool distributions are paid from, if any
+    pub fn get_pool(e: Env) -> Option<SharePool> {
+        storage::get_pool(&e)
+    }
+
+    /// Fetch the allowlist registry claims are checked against, if any
+    pub fn get_registry(e: Env) -> Option<Address> {
+        storage::get_registry(&e)
+    }
+
+    /// Fetch the amount of each token locked for a user, if any
+    pub fn get_lock(e: Env, user: Address) -> Option<Vec<i128>> {
+        storage::get_lock(&e, &user)
+    }
+
+    /// Fetch the number of ledgers between finalization and claims opening
+    pub fn get_timelock(e: Env) -> u32 {
+        storage::get_timelock(&e)
+    }
+
+    /// Fetch the ledger sequence claims open at, if the distribution has been finalized
+    pub fn get_unlock_ledger(e: Env) -> Option<u32> {
+        storage::get_unlock_ledger(&e)
+    }
+
+    /// Fetch the rolling hash over every allocation written to the contract, in order.
+    ///
+    /// Starting from 32 zero bytes, each write of `amounts` for `user` (including revocations,
+    /// written as 0 for each token) updates the hash to:
+    ///
+    /// `sha256(hash || xdr(ScVal::Address(user)) || amount_0 || ... || amount_n)`
+    ///
+    /// where each amount is 16 byte big-endian, with one amount per token in the order the
+    /// tokens were registered.
+    pub fn get_allocation_hash(e: Env) -> BytesN<32> {
+        storage::get_allocation_hash(&e)
+    }
+
+    /// Fetch the aggregate progress of the distribution
+    pub fn get_status(e: Env) -> DistributionStatus {
+        storage::get_status(&e)
+    }
+
+    /// Fetch the number of recipients that have been set a distribution
+    pub fn get_recipient_count(e: Env) -> u32 {
+        storage::get_recipient_count(&e)
+    }
+
+    /// Fetch a page of recipients, in the order they were first set a distribution, along with
+    /// their current distribution of each token and whether they have claimed it
+    ///
+    /// ### Arguments
+    /// * `page` - The page of recipients to fetch, where each page contains up to 100 recipients
+    pub fn get_recipients(e: Env, page: u32) -> Vec<(Address, Vec<i128>, bool)> {
+        let mut recipients = Vec::new(&e);
+        for user in storage::get_recipients(&e, page) {
+            let amounts = storage::get_distribution(&e, &user).unwrap_or_else(|| Vec::new(&e));
+            let claimed = storage::has_claimed(&e, &user);
+            recipients.push_back((user, amounts, claimed));
+        }
+        recipients
+    }
+
+    /// Fetch the version of the storage layout, or 0 if the contract has not been initialized
+    pub fn get_schema_version(e: Env) -> u32 {
+        storage::get_schema_version(&e)
+    }
+
+    /// Check if the admin has renounced the ability to upgrade the contract
+    pub fn get_upgrade_renounced(e: Env) -> bool {
+        storage::get_upgrade_renounced(&e)
+    }
+
+    /// Check if a user is blocked from receiving distributions
+    pub fn is_blocked(e: Env, user: Address) -> bool {
+        storage::is_blocked(&e, &user)
     }
 
     //********** Read-Write ***********//
 
-    /// (Admin Only) Set the distribution for users
+    /// (Admin Only) Set the distribution for users. Can be called after the distribution
+    /// has been finalized until the timelock elapses, emitting an event for each correction.
     ///
     /// ### Arguments
-    /// * `distributions` - The distributions to set
+    /// * `distributions` - The distributions to set, as a list of `(token_index, amount)` for each
+    ///                     user. Any token not included is set to 0 for the user.
     ///
     /// ### Panics
-    /// * `AlreadyFinalizedError` - If the contract has already been finalized
-    pub fn set_distribution(e: Env, distributions: Vec<(Address, i128)>) {
+    /// * `AlreadyFinalizedError` - If the contract has been finalized and the timelock has elapsed
+    /// * `InvalidTokenError` - If a token index is not registered or is repeated for a user
+    /// * `NegativeAmountError` - If a distribution amount is negative
+    /// * `BlockedError` - If a user is blocked
+    /// * `TtlError` - If the distributions cannot be stored until the deadline
+    pub fn set_distribution(e: Env, distributions: Vec<(Address, Vec<(u32, i128)>)>) {
         storage::get_admin(&e).require_auth();
+        let unlock_ledger = storage::get_unlock_ledger(&e);
         assert_with_error!(
             &e,
-            !storage::is_finalized(&e),
+            unlock_ledger.map_or(true, |ledger| e.ledger().sequence() < ledger),
             ContractError::AlreadyFinalizedError
         );
+        assert_with_error!(
+            &e,
+            storage::get_temporary_ttl(&e) < e.storage().max_ttl(),
+            ContractError::TtlError
+        );
         storage::extend_instance(&e);
 
-        for (user, amount) in distributions {
-            storage::set_distribution(&e, &user, amount);
+        let token_count = storage::get_tokens(&e).len();
+        let mut status = storage::get_status(&e);
+        let prev_total_allocated = status.total_allocated.clone();
+        let mut hash = storage::get_allocation_hash(&e);
+        let mut new_recipients = Vec::new(&e);
+        for (user, allocation) in distributions {
+            assert_with_error!(
+                &e,
+                !storage::is_blocked(&e, &user),
+                ContractError::BlockedError
+            );
+            let mut amounts = zero_amounts(&e, token_count);
+            for (position, (index, amount)) in allocation.iter().enumerate() {
+                assert_with_error!(&e, index < token_count, ContractError::InvalidTokenError);
+                assert_with_error!(
+                    &e,
+                    allocation
+                        .iter()
+                        .take(position)
+                        .all(|(prev_index, _)| prev_index != index),
+                    ContractError::InvalidTokenError
+                );
+                assert_with_error!(&e, amount >= 0, ContractError::NegativeAmountError);
+                amounts.set(index, amount);
+            }
+            match storage::get_distribution(&e, &user) {
+                Some(prev_amounts) => {
+                    status.total_allocated = sub_amounts(&status.total_allocated, &prev_amounts);
+                }
+                None => new_recipients.push_back(user.clone()),
+            }
+            status.total_allocated = add_amounts(&status.total_allocated, &amounts);
+            storage::set_distribution(&e, &user, &amounts);
+            hash = roll_allocation_hash(&e, &hash, &user, &amounts);
+            if unlock_ledger.is_some() {
+                ContractEvents::set_distribution(&e, user, amounts);
+            }
         }
+        if let Some(mut pool) = storage::get_pool(&e) {
+            // corrections during the timelock change the shares the pool is split by
+            pool.total_shares = sub_amounts(
+                &add_amounts(&pool.total_shares, &status.total_allocated),
+                &prev_total_allocated,
+            );
+            storage::set_pool(&e, &pool);
+        }
+        storage::set_status(&e, &status);
+        storage::set_allocation_hash(&e, &hash);
+        storage::add_recipients(&e, &new_recipients);
     }
 
-    /// (Admin Only) Finalize the distribution
+    /// (Admin Only) Set the root of a Merkle tree of allocations, claimable with `claim_merkle`.
+    /// Can be called until the timelock elapses, like `set_distribution`.
+    ///
+    /// Each leaf is `sha256(0x00 || xdr(ScVal::Address(user)) || amount_0 || .. || amount_n)`,
+    /// with one 16 byte big-endian amount per token. Each node is `sha256(0x01 || a || b)`, where
+    /// `a` and `b` are its children ordered by byte value.
+    ///
+    /// ### Arguments
+    /// * `root` - The root of the Merkle tree
+    /// * `totals` - The sum of all allocations in the tree of each token
+    ///
+    /// ### Panics
+    /// * `AlreadyFinalizedError` - If the contract has been finalized and the timelock has elapsed
+    /// * `InvalidTokenError` - If the totals do not have one amount per token
+    /// * `NegativeAmountError` - If a total is negative
+    pub fn set_merkle_root(e: Env, root: BytesN<32>, totals: Vec<i128>) {
+        storage::get_admin(&e).require_auth();
+        let unlock_ledger = storage::get_unlock_ledger(&e);
+        assert_with_error!(
+            &e,
+            unlock_ledger.map_or(true, |ledger| e.ledger().sequence() < ledger),
+            ContractError::AlreadyFinalizedError
+        );
+        assert_with_error!(
+            &e,
+            totals.len() == storage::get_tokens(&e).len(),
+            ContractError::InvalidTokenError
+        );
+        assert_with_error!(
+            &e,
+            totals.iter().all(|amount| amount >= 0),
+            ContractError::NegativeAmountError
+        );
+        storage::extend_instance(&e);
+
+        let mut status = storage::get_status(&e);
+        let prev_total_allocated = status.total_allocated.clone();
+        if let Some(prev_merkle_root) = storage::get_merkle_root(&e) {
+            status.total_allocated = sub_amounts(&status.total_allocated, &prev_merkle_root.totals);
+        }
+        status.total_allocated = add_amounts(&status.total_allocated, &totals);
+        if let Some(mut pool) = storage::get_pool(&e) {
+            pool.total_shares = sub_amounts(
+                &add_amounts(&pool.total_shares, &status.total_allocated),
+                &prev_total_allocated,
+            );
+            storage::set_pool(&e, &pool);
+        }
+        storage::set_status(&e, &status);
+        storage::set_merkle_root(
+            &e,
+            &MerkleRoot {
+                root: root.clone(),
+                totals: totals.clone(),
+            },
+        );
+
+        ContractEvents::set_merkle_root(&e, root, totals);
+    }
+
+    /// (Admin Only) Finalize the distribution. Claims open once the timelock has elapsed.
+    ///
+    /// If a pool is provided, distributions are treated as shares and each user is paid
+    /// `pool * shares / total_shares` of each token, rounded down. Rounding dust and the pool
+    /// of revoked shares are not redistributed and can be returned with `refund`.
+    ///
+    /// ### Arguments
+    /// * `expected_hash` - The allocation hash the uploaded distributions must match, if any
+    /// * `pool` - The amount of each token to split between users by their shares, if any
     ///
     /// ### Panics
     /// * `AlreadyFinalizedError` - If the contract has already been finalized
-    pub fn finalize(e: Env) {
+    /// * `AllocationHashError` - If the allocation hash does not match the expected hash
+    /// * `InvalidTokenError` - If the pool does not have one amount per token
+    /// * `NegativeAmountError` - If a pool amount is negative
+    /// * `DeadlineError` - If the timelock would elapse after the deadline
+    pub fn finalize(e: Env, expected_hash: Option<BytesN<32>>, pool: Option<Vec<i128>>) {
         storage::get_admin(&e).require_auth();
 
         assert_with_error!(
@@ -101,9 +388,129 @@ impl Distributor {
             !storage::is_finalized(&e),
             ContractError::AlreadyFinalizedError
         );
+        if let Some(expected_hash) = expected_hash {
+            assert_with_error!(
+                &e,
+                storage::get_allocation_hash(&e) == expected_hash,
+                ContractError::AllocationHashError
+            );
+        }
+        if let Some(pool) = pool {
+            assert_with_error!(
+                &e,
+                pool.len() == storage::get_tokens(&e).len(),
+                ContractError::InvalidTokenError
+            );
+            assert_with_error!(
+                &e,
+                pool.iter().all(|amount| amount >= 0),
+                ContractError::NegativeAmountError
+            );
+            storage::set_pool(
+                &e,
+                &SharePool {
+                    amounts: pool,
+                    total_shares: storage::get_status(&e).total_allocated,
+                },
+            );
+        }
+        let unlock_ledger = e
+            .ledger()
+            .sequence()
+            .checked_add(storage::get_timelock(&e))
+            .unwrap_or_else(|| panic_with_error!(&e, ContractError::DeadlineError));
+        assert_with_error!(
+            &e,
+            unlock_ledger <= storage::get_deadline(&e),
+            ContractError::DeadlineError
+        );
+        storage::extend_instance(&e);
+
+        storage::set_finalized(&e, &unlock_ledger);
+        ContractEvents::finalize(&e, unlock_ledger);
+    }
+
+    /// (Admin Only) Revoke the distribution for users. Can be called after the distribution
+    /// has been finalized.
+    ///
+    /// With a share pool, revoking during the timelock lowers the total shares like setting the
+    /// distribution to 0, so the pool is split between the remaining users. Shares revoked after
+    /// the timelock are not redistributed, and their pool can be returned with `refund`.
+    ///
+    /// ### Arguments
+    /// * `users` - The users to revoke the distribution for
+    ///
+    /// ### Panics
+    /// * `AlreadyClaimedError` - If a user has already claimed their distribution
+    /// * `NoDistributionError` - If a user has no distribution to revoke
+    pub fn revoke(e: Env, users: Vec<Address>) {
+        storage::get_admin(&e).require_auth();
+        storage::extend_instance(&e);
+
+        let mut status = storage::get_status(&e);
+        let prev_total_allocated = status.total_allocated.clone();
+        let mut hash = storage::get_allocation_hash(&e);
+        let zero_amounts = zero_amounts(&e, status.total_allocated.len());
+        for user in users {
+            assert_with_error!(
+                &e,
+                !storage::has_claimed(&e, &user),
+                ContractError::AlreadyClaimedError
+            );
+            let amounts = storage::get_distribution(&e, &user).unwrap_or(zero_amounts.clone());
+            assert_with_error!(
+                &e,
+                amounts.iter().any(|amount| amount > 0),
+                ContractError::NoDistributionError
+            );
+
+            storage::set_distribution(&e, &user, &zero_amounts);
+            status.total_allocated = sub_amounts(&status.total_allocated, &amounts);
+            hash = roll_allocation_hash(&e, &hash, &user, &zero_amounts);
+
+            ContractEvents::revoke(&e, user, amounts);
+        }
+        if let Some(mut pool) = storage::get_pool(&e) {
+            let unlock_ledger = storage::get_unlock_ledger(&e).unwrap_optimized();
+            if e.ledger().sequence() < unlock_ledger {
+                pool.total_shares = sub_amounts(
+                    &add_amounts(&pool.total_shares, &status.total_allocated),
+                    &prev_total_allocated,
+                );
+                storage::set_pool(&e, &pool);
+            }
+        }
+        storage::set_status(&e, &status);
+        storage::set_allocation_hash(&e, &hash);
+    }
+
+    /// (Admin Only) Block users from receiving distributions. Blocked users cannot claim, be sent
+    /// tokens via `claim_to`, withdraw locked tokens, or have their distribution set.
+    ///
+    /// ### Arguments
+    /// * `users` - The users to block
+    pub fn block(e: Env, users: Vec<Address>) {
+        storage::get_admin(&e).require_auth();
         storage::extend_instance(&e);
 
-        storage::set_finalized(&e);
+        for user in users {
+            storage::set_blocked(&e, &user);
+            ContractEvents::block(&e, user);
+        }
+    }
+
+    /// (Admin Only) Unblock users, restoring any unclaimed distribution
+    ///
+    /// ### Arguments
+    /// * `users` - The users to unblock
+    pub fn unblock(e: Env, users: Vec<Address>) {
+        storage::get_admin(&e).require_auth();
+        storage::extend_instance(&e);
+
+        for user in users {
+            storage::remove_blocked(&e, &user);
+            ContractEvents::unblock(&e, user);
+        }
     }
 
     /// (Admin Only) Set the admin of the contract
@@ -115,67 +522,520 @@ impl Distributor {
         storage::set_admin(&e, &admin);
     }
 
-    /// Claim the distribution
+    /// (Admin Only) Upgrade the contract to new wasm. Storage is kept across the upgrade. If the
+    /// new wasm uses a newer storage layout, `migrate` must be called before any other function.
+    ///
+    /// ### Arguments
+    /// * `new_wasm_hash` - The hash of the uploaded wasm to upgrade to
+    ///
+    /// ### Panics
+    /// * `UpgradeError` - If the admin has renounced the ability to upgrade the contract
+    pub fn upgrade(e: Env, new_wasm_hash: BytesN<32>) {
+        storage::get_admin(&e).require_auth();
+        assert_with_error!(
+            &e,
+            !storage::get_upgrade_renounced(&e),
+            ContractError::UpgradeError
+        );
+        storage::extend_instance(&e);
+
+        e.deployer()
+            .update_current_contract_wasm(new_wasm_hash.clone());
+        ContractEvents::upgrade(&e, new_wasm_hash);
+    }
+
+    /// (Admin Only) Permanently renounce the ability to upgrade the contract
+    pub fn renounce_upgrade(e: Env) {
+        storage::get_admin(&e).require_auth();
+        storage::extend_instance(&e);
+
+        storage::set_upgrade_renounced(&e);
+        ContractEvents::renounce_upgrade(&e);
+    }
+
+    /// (Admin Only) Migrate the storage of the contract from an older layout to the layout of the
+    /// current wasm. The current layout is the first versioned layout, so there is no older
+    /// layout to convert yet. Wasm that changes the layout bumps `SCHEMA_VERSION` and converts
+    /// the previous layout here.
+    ///
+    /// ### Panics
+    /// * `MigrationError` - If the contract is not initialized or already uses the current layout
+    pub fn migrate(e: Env) {
+        let version = storage::get_schema_version(&e);
+        assert_with_error!(
+            &e,
+            version != 0 && version < SCHEMA_VERSION,
+            ContractError::MigrationError
+        );
+        storage::get_admin(&e).require_auth();
+        storage::extend_instance(&e);
+
+        storage::set_schema_version(&e, &SCHEMA_VERSION);
+        ContractEvents::migrate(&e, version, SCHEMA_VERSION);
+    }
+
+    /// Claim the distribution of every token for a user. If a claim fee is configured, the fee
+    /// (rounded down) is sent to the fee recipient and the remainder to the user.
+    ///
+    /// If a token cannot be transferred to the user or the fee recipient (e.g. a missing account,
+    /// missing trustline or unauthorized trustline) the claim fails with `TransferError`, and the
+    /// distribution remains claimable once resolved.
     ///
     /// ### Arguments
     /// * `user` - The user to claim the distribution for
     ///
     /// ### Panics
     /// * `NotFinalizedError` - If the contract has not been finalized
+    /// * `TimelockError` - If the timelock has not elapsed
     /// * `AlreadyClaimedError` - If the user has already claimed their distribution
     /// * `DeadlineError` - If the deadline has passed
     /// * `NoDistributionError` - If the user has no distribution to claim
-    pub fn claim(e: Env, user: Address) -> i128 {
+    /// * `NotAllowedError` - If the allowlist registry does not allow the user
+    /// * `BlockedError` - If the user is blocked
+    /// * `TransferError` - If a token transfer fails
+    ///
+    /// ### Returns
+    /// The amount of each token sent to the user, after fees
+    pub fn claim(e: Env, user: Address) -> Vec<i128> {
         user.require_auth();
+        let (amounts, fees) = claim_internal(&e, &user, stored_allocation(&e, &user));
+        transfer_amounts(&e, &user, &amounts);
+
+        ContractEvents::claim(&e, user, amounts.clone(), fees);
+        amounts
+    }
+
+    /// Claim the allocation of every token for a user from the Merkle tree set with
+    /// `set_merkle_root`. A user can only claim once, either from the tree or their distribution.
+    ///
+    /// ### Arguments
+    /// * `user` - The user to claim the allocation for
+    /// * `amounts` - The amount of each token allocated to the user in the tree
+    /// * `proof` - The sibling hashes from the user's leaf to the root
+    ///
+    /// ### Panics
+    /// * `MerkleProofError` - If no Merkle root is set or the proof is invalid
+    /// * `NotFinalizedError` - If the contract has not been finalized
+    /// * `TimelockError` - If the timelock has not elapsed
+    /// * `AlreadyClaimedError` - If the user has already claimed
+    /// * `DeadlineError` - If the deadline has passed
+    /// * `NoDistributionError` - If the user has no allocation to claim
+    /// * `NotAllowedError` - If the allowlist registry does not allow the user
+    /// * `BlockedError` - If the user is blocked
+    /// * `TransferError` - If a token cannot be transferred to the user
+    ///
+    /// ### Returns
+    /// The amount of each token sent to the user, after fees
+    pub fn claim_merkle(
+        e: Env,
+        user: Address,
+        amounts: Vec<i128>,
+        proof: Vec<BytesN<32>>,
+    ) -> Vec<i128> {
+        user.require_auth();
+        let merkle_root = storage::get_merkle_root(&e);
         assert_with_error!(
             &e,
-            storage::is_finalized(&e),
-            ContractError::NotFinalizedError
+            merkle_root.is_some()
+                && amounts.len() == storage::get_tokens(&e).len()
+                && verify_merkle_proof(
+                    &e,
+                    &merkle_root.unwrap_optimized().root,
+                    merkle_leaf(&e, &user, &amounts),
+                    &proof
+                ),
+            ContractError::MerkleProofError
         );
+
+        let (amounts, fees) = claim_internal(&e, &user, amounts);
+        transfer_amounts(&e, &user, &amounts);
+
+        ContractEvents::claim(&e, user, amounts.clone(), fees);
+        amounts
+    }
+
+    /// Claim the distribution of every token for a user into a receiver contract. The tokens are
+    /// sent to `receiver`, then `on_distribution_received` is invoked on `receiver` for each token
+    /// sent, so the tokens can be deposited on behalf of the user atomically.
+    ///
+    /// ### Arguments
+    /// * `user` - The user to claim the distribution for
+    /// * `receiver` - The contract implementing `DistributionReceiver` to send the tokens to
+    ///
+    /// ### Panics
+    /// * `NotFinalizedError` - If the contract has not been finalized
+    /// * `TimelockError` - If the timelock has not elapsed
+    /// * `AlreadyClaimedError` - If the user has already claimed their distribution
+    /// * `DeadlineError` - If the deadline has passed
+    /// * `NoDistributionError` - If the user has no distribution to claim
+    /// * `NotAllowedError` - If the allowlist registry does not allow the user
+    /// * `BlockedError` - If the user or receiver is blocked
+    /// * `TransferError` - If a token cannot be transferred to the receiver
+    ///
+    /// ### Returns
+    /// The amount of each token sent to the receiver, after fees
+    pub fn claim_to(e: Env, user: Address, receiver: Address) -> Vec<i128> {
+        user.require_auth();
         assert_with_error!(
             &e,
-            !storage::has_claimed(&e, &user),
-            ContractError::AlreadyClaimedError
+            !storage::is_blocked(&e, &receiver),
+            ContractError::BlockedError
         );
+        let (amounts, fees) = claim_internal(&e, &user, stored_allocation(&e, &user));
+        transfer_amounts(&e, &receiver, &amounts);
+
+        let receiver_client = DistributionReceiverClient::new(&e, &receiver);
+        for (token, amount) in storage::get_tokens(&e).iter().zip(amounts.iter()) {
+            if amount > 0 {
+                receiver_client.on_distribution_received(
+                    &e.current_contract_address(),
+                    &user,
+                    &token,
+                    &amount,
+                );
+            }
+        }
+
+        ContractEvents::claim(&e, user, amounts.clone(), fees);
+        amounts
+    }
+
+    /// Claim the distribution of every token for a user into a lock, in exchange for the lock
+    /// bonus. The amount locked is the distribution after fees times the bonus multiplier
+    /// (rounded down), and can be withdrawn with `withdraw_lock` at the release ledger.
+    ///
+    /// ### Arguments
+    /// * `user` - The user to claim the distribution for
+    ///
+    /// ### Panics
+    /// * `LockError` - If the lock bonus is not enabled
+    /// * `NotFinalizedError` - If the contract has not been finalized
+    /// * `TimelockError` - If the timelock has not elapsed
+    /// * `AlreadyClaimedError` - If the user has already claimed their distribution
+    /// * `DeadlineError` - If the deadline has passed
+    /// * `NoDistributionError` - If the user has no distribution to claim
+    /// * `NotAllowedError` - If the allowlist registry does not allow the user
+    /// * `BlockedError` - If the user is blocked
+    /// * `OverflowError` - If a bonus-multiplied amount overflows
+    /// * `BalanceError` - If the contract does not hold enough tokens to fund the lock bonus
+    ///
+    /// ### Returns
+    /// The amount of each token locked for the user
+    pub fn claim_locked(e: Env, user: Address) -> Vec<i128> {
+        user.require_auth();
+        let lock_bonus = storage::get_lock_bonus(&e);
+        assert_with_error!(&e, lock_bonus.is_some(), ContractError::LockError);
+        let lock_bonus = lock_bonus.unwrap_optimized();
+        let multiplier_bps = lock_bonus.multiplier_bps as i128;
+
+        let (amounts, fees) = claim_internal(&e, &user, stored_allocation(&e, &user));
+        let mut locked_amounts = Vec::new(&e);
+        for amount in amounts.iter() {
+            let locked_amount = amount
+                .checked_mul(multiplier_bps)
+                .map(|amount| amount / 10_000)
+                .unwrap_or_else(|| panic_with_error!(&e, ContractError::OverflowError));
+            locked_amounts.push_back(locked_amount);
+        }
+
+        let mut status = storage::get_status(&e);
+        status.total_locked = add_amounts(&status.total_locked, &locked_amounts);
+        storage::set_status(&e, &status);
+        storage::set_lock(&e, &user, &locked_amounts, lock_bonus.release_ledger);
+
+        // the contract must hold enough tokens for all unclaimed distributions and all locks
+        let unclaimed_amounts = unclaimed_amounts(&e, &status);
+        for (index, token) in storage::get_tokens(&e).iter().enumerate() {
+            let index = index as u32;
+            let owed =
+                unclaimed_amounts.get_unchecked(index) + status.total_locked.get_unchecked(index);
+            let balance = TokenClient::new(&e, &token).balance(&e.current_contract_address());
+            assert_with_error!(&e, balance >= owed, ContractError::BalanceError);
+        }
+
+        ContractEvents::claim_locked(&e, user, locked_amounts.clone(), fees);
+        locked_amounts
+    }
+
+    /// Withdraw the tokens locked for a user once the release ledger is reached. Locked tokens
+    /// can be withdrawn after the deadline.
+    ///
+    /// ### Arguments
+    /// * `user` - The user to withdraw the locked tokens for
+    ///
+    /// ### Panics
+    /// * `LockError` - If the lock bonus is not enabled
+    /// * `StillLockedError` - If the release ledger has not been reached
+    /// * `NoDistributionError` - If the user has no locked tokens
+    /// * `BlockedError` - If the user is blocked
+    /// * `TransferError` - If a token cannot be transferred to the user
+    ///
+    /// ### Returns
+    /// The amount of each token withdrawn
+    pub fn withdraw_lock(e: Env, user: Address) -> Vec<i128> {
+        user.require_auth();
+        let lock_bonus = storage::get_lock_bonus(&e);
+        assert_with_error!(&e, lock_bonus.is_some(), ContractError::LockError);
         assert_with_error!(
             &e,
-            e.ledger().sequence() <= storage::get_deadline(&e),
-            ContractError::DeadlineError
+            e.ledger().sequence() >= lock_bonus.unwrap_optimized().release_ledger,
+            ContractError::StillLockedError
         );
         storage::extend_instance(&e);
 
-        let amount = storage::get_distribution(&e, &user);
-        assert_with_error!(&e, amount > 0, ContractError::NoDistributionError);
+        assert_with_error!(
+            &e,
+            !storage::is_blocked(&e, &user),
+            ContractError::BlockedError
+        );
 
-        storage::set_claimed(&e, &user);
+        let locked_amounts = storage::get_lock(&e, &user);
+        assert_with_error!(
+            &e,
+            locked_amounts.is_some(),
+            ContractError::NoDistributionError
+        );
+        let locked_amounts = locked_amounts.unwrap_optimized();
+        storage::remove_lock(&e, &user);
 
-        let token = storage::get_token(&e);
-        TokenClient::new(&e, &token).transfer(&e.current_contract_address(), &user, &amount);
+        let mut status = storage::get_status(&e);
+        status.total_locked = sub_amounts(&status.total_locked, &locked_amounts);
+        storage::set_status(&e, &status);
 
-        ContractEvents::claim(&e, user, amount);
-        amount
+        transfer_amounts(&e, &user, &locked_amounts);
+
+        ContractEvents::withdraw_lock(&e, user, locked_amounts.clone());
+        locked_amounts
     }
 
-    /// Refund the remaining balance to the admin
+    /// Refund the remaining balance of each token to the admin, excluding any tokens still
+    /// locked for users. The refund event includes the aggregate claim progress of the
+    /// distribution.
     ///
     /// ### Panics
     /// * `DeadlineError` - If the deadline has not passed
-    pub fn refund(e: Env) -> i128 {
+    pub fn refund(e: Env) -> Vec<i128> {
         assert_with_error!(
             &e,
             e.ledger().sequence() > storage::get_deadline(&e),
             ContractError::DeadlineError
         );
-        let token = storage::get_token(&e);
-        let token_client = TokenClient::new(&e, &token);
+        let admin = storage::get_admin(&e);
+        let status = storage::get_status(&e);
+
+        let mut balances = Vec::new(&e);
+        for (index, token) in storage::get_tokens(&e).iter().enumerate() {
+            let token_client = TokenClient::new(&e, &token);
+            // nothing is refunded if the balance has drifted below the locked total
+            let balance = (token_client.balance(&e.current_contract_address())
+                - status.total_locked.get_unchecked(index as u32))
+            .max(0);
+            if balance > 0 {
+                token_client.transfer(&e.current_contract_address(), &admin, &balance);
+            }
+            balances.push_back(balance);
+        }
+
+        ContractEvents::refund(&e, admin, balances.clone(), status);
+        balances
+    }
+}
+
+/// Roll the allocation hash forward with an allocation write
+fn roll_allocation_hash(
+    e: &Env,
+    hash: &BytesN<32>,
+    user: &Address,
+    amounts: &Vec<i128>,
+) -> BytesN<32> {
+    let mut data = Bytes::from(hash.clone());
+    data.append(&user.clone().to_xdr(e));
+    for amount in amounts.iter() {
+        data.extend_from_array(&amount.to_be_bytes());
+    }
+    e.crypto().sha256(&data)
+}
+
+/// Compute the Merkle leaf of a user's allocation
+fn merkle_leaf(e: &Env, user: &Address, amounts: &Vec<i128>) -> BytesN<32> {
+    let mut data = Bytes::from_array(e, &[0]);
+    data.append(&user.clone().to_xdr(e));
+    for amount in amounts.iter() {
+        data.extend_from_array(&amount.to_be_bytes());
+    }
+    e.crypto().sha256(&data)
+}
+
+/// Check that a Merkle proof hashes from the leaf to the root
+fn verify_merkle_proof(
+    e: &Env,
+    root: &BytesN<32>,
+    leaf: BytesN<32>,
+    proof: &Vec<BytesN<32>>,
+) -> bool {
+    let mut hash = leaf;
+    for sibling in proof.iter() {
+        let mut data = Bytes::from_array(e, &[1]);
+        if hash.to_array() <= sibling.to_array() {
+            data.append(&Bytes::from(hash));
+            data.append(&Bytes::from(sibling));
+        } else {
+            data.append(&Bytes::from(sibling));
+            data.append(&Bytes::from(hash));
+        }
+        hash = e.crypto().sha256(&data);
+    }
+    hash == *root
+}
+
+/// Create a list of zero amounts, one for each token
+fn zero_amounts(e: &Env, token_count: u32) -> Vec<i128> {
+    let mut amounts = Vec::new(e);
+    for _ in 0..token_count {
+        amounts.push_back(0);
+    }
+    amounts
+}
+
+/// Add each token amount in `b` to the amount for the same token in `a`
+fn add_amounts(a: &Vec<i128>, b: &Vec<i128>) -> Vec<i128> {
+    let mut result = a.clone();
+    for (index, amount) in b.iter().enumerate() {
+        result.set(index as u32, a.get_unchecked(index as u32) + amount);
+    }
+    result
+}
+
+/// Subtract each token amount in `b` from the amount for the same token in `a`
+fn sub_amounts(a: &Vec<i128>, b: &Vec<i128>) -> Vec<i128> {
+    let mut result = a.clone();
+    for (index, amount) in b.iter().enumerate() {
+        result.set(index as u32, a.get_unchecked(index as u32) - amount);
+    }
+    result
+}
+
+/// Get the distribution of each token set for a user, or an empty list if none has been set
+fn stored_allocation(e: &Env, user: &Address) -> Vec<i128> {
+    storage::get_distribution(e, user).unwrap_or_else(|| Vec::new(e))
+}
+
+/// Mark the distribution of a user as claimed and send any claim fee to the fee recipient
+///
+/// Returns the amount of each token owed to the user after fees, and the fee taken for each token
+fn claim_internal(e: &Env, user: &Address, allocation: Vec<i128>) -> (Vec<i128>, Vec<i128>) {
+    let unlock_ledger = storage::get_unlock_ledger(e);
+    assert_with_error!(e, unlock_ledger.is_some(), ContractError::NotFinalizedError);
+    assert_with_error!(
+        e,
+        e.ledger().sequence() >= unlock_ledger.unwrap_optimized(),
+        ContractError::TimelockError
+    );
+    assert_with_error!(
+        e,
+        !storage::has_claimed(e, user),
+        ContractError::AlreadyClaimedError
+    );
+    assert_with_error!(
+        e,
+        e.ledger().sequence() <= storage::get_deadline(e),
+        ContractError::DeadlineError
+    );
+    storage::extend_instance(e);
+
+    let amounts = match storage::get_pool(e) {
+        Some(pool) => pool_amounts(e, &pool, &allocation),
+        None => allocation,
+    };
+    assert_with_error!(
+        e,
+        amounts.iter().any(|amount| amount > 0),
+        ContractError::NoDistributionError
+    );
+
+    assert_with_error!(
+        e,
+        !storage::is_blocked(e, user),
+        ContractError::BlockedError
+    );
+    if let Some(registry) = storage::get_registry(e) {
+        assert_with_error!(
+            e,
+            AllowlistRegistryClient::new(e, &registry).is_allowed(user),
+            ContractError::NotAllowedError
+        );
+    }
+
+    storage::set_claimed(e, user);
+
+    let fee = storage::get_fee(e);
+    let mut user_amounts = Vec::new(e);
+    let mut fees = Vec::new(e);
+    for amount in amounts.iter() {
+        let fee_amount = match &fee {
+            Some(fee) => amount
+                .checked_mul(fee.rate_bps as i128)
+                .map(|amount| amount / 10_000)
+                .unwrap_or_else(|| panic_with_error!(e, ContractError::OverflowError)),
+            None => 0,
+        };
+        user_amounts.push_back(amount - fee_amount);
+        fees.push_back(fee_amount);
+    }
+
+    let mut status = storage::get_status(e);
+    status.total_claimed = add_amounts(&status.total_claimed, &amounts);
+    status.total_fees = add_amounts(&status.total_fees, &fees);
+    status.claim_count += 1;
+    storage::set_status(e, &status);
 
-        let balance = token_client.balance(&e.current_contract_address());
+    if let Some(fee) = fee {
+        transfer_amounts(e, &fee.recipient, &fees);
+    }
 
-        if balance > 0 {
-            let admin = storage::get_admin(&e);
-            token_client.transfer(&e.current_contract_address(), &admin, &balance);
+    (user_amounts, fees)
+}
+
+/// Convert a user's shares of each token into the amount of each token they are paid from the
+/// pool, rounded down
+fn pool_amounts(e: &Env, pool: &SharePool, shares: &Vec<i128>) -> Vec<i128> {
+    let mut amounts = Vec::new(e);
+    for (index, share) in shares.iter().enumerate() {
+        let index = index as u32;
+        let total_shares = pool.total_shares.get_unchecked(index);
+        if total_shares == 0 {
+            amounts.push_back(0);
+            continue;
         }
+        let amount = share
+            .checked_mul(pool.amounts.get_unchecked(index))
+            .and_then(|amount| amount.checked_div(total_shares))
+            .unwrap_or_else(|| panic_with_error!(e, ContractError::OverflowError));
+        amounts.push_back(amount);
+    }
+    amounts
+}
 
-        balance
+/// Get the amount of each token still owed to users that have not claimed
+fn unclaimed_amounts(e: &Env, status: &DistributionStatus) -> Vec<i128> {
+    match storage::get_pool(e) {
+        Some(pool) => sub_amounts(&pool.amounts, &status.total_claimed),
+        None => sub_amounts(&status.total_allocated, &status.total_claimed),
+    }
+}
+
+/// Send each token amount to `to`
+fn transfer_amounts(e: &Env, to: &Address, amounts: &Vec<i128>) {
+    for (token, amount) in storage::get_tokens(e).iter().zip(amounts.iter()) {
+        if amount > 0 {
+            let result = TokenClient::new(e, &token).try_transfer(
+                &e.current_contract_address(),
+                to,
+                &amount,
+            );
+            if result.is_err() {
+                panic_with_error!(e, ContractError::TransferError);
+            }
+        }
     }
 }
@@ -0,0 +1,226 @@
+//! Resource cost estimates for `set_distribution` batches and `claim`, measured against the
+//! optimized wasm with `env.budget()` and the recorded ledger footprint.
+//!
+//! Print the table with `cargo test --features wasm-tests cost_estimate -- --nocapture`.
+
+use std::{println, rc::Rc, vec::Vec};
+
+use crate::{
+    storage::ONE_DAY_LEDGERS, test::distributor_wasm, testutils::EnvTestUtils, DistributorClient,
+};
+use soroban_env_host::storage::AccessType;
+use soroban_sdk::{
+    testutils::Address as _,
+    token::StellarAssetClient,
+    vec,
+    xdr::{Limits, WriteXdr},
+    Address, Env,
+};
+
+// Network limits and resource fees per transaction, as configured on mainnet for protocol 20.
+// Fees are in stroops. Rent, events and transaction size fees are not included.
+const TX_MAX_INSTRUCTIONS: u64 = 100_000_000;
+const TX_MAX_MEMORY_BYTES: u64 = 40 * 1024 * 1024;
+const TX_MAX_READ_ENTRIES: u32 = 40;
+const TX_MAX_WRITE_ENTRIES: u32 = 25;
+const TX_MAX_READ_BYTES: u32 = 200 * 1024;
+const TX_MAX_WRITE_BYTES: u32 = 65 * 1024;
+const FEE_PER_10K_INSTRUCTIONS: u64 = 25;
+const FEE_READ_ENTRY: u64 = 6_250;
+const FEE_WRITE_ENTRY: u64 = 10_000;
+const FEE_READ_1KB: u64 = 1_786;
+const FEE_WRITE_1KB: u64 = 11_800;
+
+const BATCH_SIZES: [u32; 5] = [1, 5, 10, 15, 20];
+const MAX_SEARCHED_BATCH_SIZE: u32 = 50;
+
+/// The resources consumed by a single contract invocation
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct ResourceCost {
+    instructions: u64,
+    memory_bytes: u64,
+    read_entries: u32,
+    write_entries: u32,
+    read_bytes: u32,
+    write_bytes: u32,
+}
+
+impl ResourceCost {
+    /// The estimated resource fee in stroops
+    fn fee(&self) -> u64 {
+        (self.instructions * FEE_PER_10K_INSTRUCTIONS).div_ceil(10_000)
+            + self.read_entries as u64 * FEE_READ_ENTRY
+            + self.write_entries as u64 * FEE_WRITE_ENTRY
+            + (self.read_bytes as u64 * FEE_READ_1KB).div_ceil(1024)
+            + (self.write_bytes as u64 * FEE_WRITE_1KB).div_ceil(1024)
+    }
+
+    /// Check if the invocation fits in a single transaction
+    fn within_limits(&self) -> bool {
+        self.instructions <= TX_MAX_INSTRUCTIONS
+            && self.memory_bytes <= TX_MAX_MEMORY_BYTES
+            && self.read_entries <= TX_MAX_READ_ENTRIES
+            && self.write_entries <= TX_MAX_WRITE_ENTRIES
+            && self.read_bytes <= TX_MAX_READ_BYTES
+            && self.write_bytes <= TX_MAX_WRITE_BYTES
+    }
+}
+
+/// Measure the resources consumed by `invoke`. Read-write entries count towards both the read
+/// and write limits, as they do on the network.
+fn measure(e: &Env, invoke: impl FnOnce()) -> ResourceCost {
+    e.host()
+        .with_mut_storage(|storage| {
+            storage.footprint = Default::default();
+            Ok(())
+        })
+        .unwrap();
+    e.budget().reset_unlimited();
+
+    invoke();
+
+    let mut cost = ResourceCost {
+        instructions: e.budget().cpu_instruction_cost(),
+        memory_bytes: e.budget().memory_bytes_cost(),
+        ..Default::default()
+    };
+    let budget = e.host().budget_cloned();
+    e.host()
+        .with_mut_storage(|storage| {
+            for (key, access) in storage.footprint.0.iter(&budget)? {
+                let size = match storage.map.get::<Rc<_>>(key, &budget)? {
+                    Some(Some((entry, _))) => entry.to_xdr(Limits::none()).unwrap().len() as u32,
+                    _ => 0,
+                };
+                cost.read_entries += 1;
+                cost.read_bytes += size;
+                if *access == AccessType::ReadWrite {
+                    cost.write_entries += 1;
+                    cost.write_bytes += size;
+                }
+            }
+            Ok(())
+        })
+        .unwrap();
+    cost
+}
+
+/// Deploy and initialize the distributor with a funded token
+fn setup(e: &Env) -> DistributorClient<'_> {
+    e.set_default_info();
+    e.mock_all_auths();
+    // only the measured invocations are subject to the network limits
+    e.budget().reset_unlimited();
+
+    let dist_id = e.register_contract_wasm(None, distributor_wasm::WASM);
+    let dist_client = DistributorClient::new(e, &dist_id);
+    let token = e.register_stellar_asset_contract(Address::generate(e));
+    StellarAssetClient::new(e, &token).mint(&dist_id, &i128::MAX);
+    dist_client.initialize(
+        &vec![e, token],
+        &(e.ledger().sequence() + 30 * ONE_DAY_LEDGERS),
+        &Address::generate(e),
+        &vec![e],
+    );
+    dist_client
+}
+
+/// Measure `set_distribution` with a batch of new recipients on a fresh contract
+fn measure_set_distribution(batch_size: u32) -> ResourceCost {
+    let e = Env::default();
+    let dist_client = setup(&e);
+    let mut distributions = vec![&e];
+    for amount in 1..=batch_size as i128 {
+        distributions.push_back((Address::generate(&e), vec![&e, (0_u32, amount)]));
+    }
+    measure(&e, || dist_client.set_distribution(&distributions))
+}
+
+/// Measure a single `claim` from a finalized distribution
+fn measure_claim() -> ResourceCost {
+    let e = Env::default();
+    let dist_client = setup(&e);
+    let user = Address::generate(&e);
+    dist_client.set_distribution(&vec![&e, (user.clone(), vec![&e, (0_u32, 100)])]);
+    dist_client.finalize(&None, &None);
+    measure(&e, || {
+        dist_client.claim(&user);
+    })
+}
+
+/// Find the largest batch size of `set_distribution` that fits in a single transaction
+fn max_batch_size() -> u32 {
+    let (mut low, mut high) = (0, MAX_SEARCHED_BATCH_SIZE);
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if measure_set_distribution(mid).within_limits() {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    low
+}
+
+fn print_row(name: &str, cost: &ResourceCost) {
+    println!(
+        "| {:<22} | {:>12} | {:>12} | {:>5} | {:>6} | {:>10} | {:>11} | {:>10} |",
+        name,
+        cost.instructions,
+        cost.memory_bytes,
+        cost.read_entries,
+        cost.write_entries,
+        cost.read_bytes,
+        cost.write_bytes,
+        cost.fee()
+    );
+}
+
+#[test]
+fn test_cost_estimate() {
+    println!(
+        "| {:<22} | {:>12} | {:>12} | {:>5} | {:>6} | {:>10} | {:>11} | {:>10} |",
+        "invocation",
+        "instructions",
+        "memory",
+        "reads",
+        "writes",
+        "read bytes",
+        "write bytes",
+        "fee"
+    );
+    let mut costs = Vec::new();
+    for batch_size in BATCH_SIZES {
+        let cost = measure_set_distribution(batch_size);
+        print_row(&std::format!("set_distribution({})", batch_size), &cost);
+        costs.push(cost);
+    }
+    let claim_cost = measure_claim();
+    print_row("claim", &claim_cost);
+
+    // verify the cost grows with the batch size
+    for pair in costs.windows(2) {
+        assert!(pair[0].instructions < pair[1].instructions);
+        assert!(pair[0].write_entries < pair[1].write_entries);
+    }
+    assert!(claim_cost.within_limits());
+
+    let batch_size = max_batch_size();
+    let batch_cost = measure_set_distribution(batch_size);
+    assert!(batch_size > 0);
+    assert!(batch_cost.within_limits());
+    assert!(!measure_set_distribution(batch_size + 1).within_limits());
+
+    let batches_per_1k = 1000_u64.div_ceil(batch_size as u64);
+    println!();
+    println!("recommended max batch size: {}", batch_size);
+    println!(
+        "estimated upload fee per 1k recipients: {} stroops ({} batches)",
+        batches_per_1k * batch_cost.fee(),
+        batches_per_1k
+    );
+    println!(
+        "estimated claim fee per 1k recipients: {} stroops",
+        1000 * claim_cost.fee()
+    );
+}
@@ -3,6 +3,7 @@ use soroban_sdk::contracterror;
 /// The error codes for the contract.
 #[contracterror]
 #[derive(Copy, Clone, PartialEq, Eq)]
+#[allow(clippy::enum_variant_names)]
 pub enum ContractError {
     // Default errors to align with built-in contract
     InternalError = 1,
@@ -21,4 +22,18 @@ pub enum ContractError {
     AlreadyClaimedError = 102,
     AlreadyFinalizedError = 103,
     NoDistributionError = 104,
+    TimelockError = 105,
+    AllocationHashError = 106,
+    InvalidTokenError = 107,
+    TransferError = 108,
+    FeeError = 109,
+    LockError = 110,
+    StillLockedError = 111,
+    NotAllowedError = 112,
+    BlockedError = 113,
+    UpgradeError = 114,
+    MigrationError = 115,
+    MerkleProofError = 116,
+    TtlError = 117,
+    OptionError = 118,
 }
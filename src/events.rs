@@ -1,13 +1,126 @@
-use soroban_sdk::{Address, Env, Symbol};
+use soroban_sdk::{Address, BytesN, Env, Symbol, Vec};
+
+use crate::types::DistributionStatus;
+
 pub struct ContractEvents {}
 
 impl ContractEvents {
-    /// Emitted when a distribution is claimed
+    /// Emitted when a distribution is claimed. `amounts` is the amount of each token sent to the
+    /// user after fees.
     ///
     /// - topics - `["dist_claim", user: Address]`
-    /// - data - `amount: i128`
-    pub fn claim(e: &Env, user: Address, amount: i128) {
-        let topics = (Symbol::new(&e, "dist_claim"), user);
-        e.events().publish(topics, amount);
+    /// - data - `[amounts: Vec<i128>, fees: Vec<i128>]`
+    pub fn claim(e: &Env, user: Address, amounts: Vec<i128>, fees: Vec<i128>) {
+        let topics = (Symbol::new(e, "dist_claim"), user);
+        e.events().publish(topics, (amounts, fees));
+    }
+
+    /// Emitted when a distribution is revoked by the admin
+    ///
+    /// - topics - `["dist_revoke", user: Address]`
+    /// - data - `amounts: Vec<i128>` (one amount per token)
+    pub fn revoke(e: &Env, user: Address, amounts: Vec<i128>) {
+        let topics = (Symbol::new(e, "dist_revoke"), user);
+        e.events().publish(topics, amounts);
+    }
+
+    /// Emitted when the distribution is finalized
+    ///
+    /// - topics - `["dist_final"]`
+    /// - data - `unlock_ledger: u32`
+    pub fn finalize(e: &Env, unlock_ledger: u32) {
+        let topics = (Symbol::new(e, "dist_final"),);
+        e.events().publish(topics, unlock_ledger);
+    }
+
+    /// Emitted when a distribution is corrected by the admin after finalization
+    ///
+    /// - topics - `["dist_set", user: Address]`
+    /// - data - `amounts: Vec<i128>` (one amount per token)
+    pub fn set_distribution(e: &Env, user: Address, amounts: Vec<i128>) {
+        let topics = (Symbol::new(e, "dist_set"), user);
+        e.events().publish(topics, amounts);
+    }
+
+    /// Emitted when the remaining balance is refunded to the admin
+    ///
+    /// - topics - `["dist_refund", admin: Address]`
+    /// - data - `[amounts: Vec<i128>, total_claimed: Vec<i128>, claim_count: u32]`
+    pub fn refund(e: &Env, admin: Address, amounts: Vec<i128>, status: DistributionStatus) {
+        let topics = (Symbol::new(e, "dist_refund"), admin);
+        e.events()
+            .publish(topics, (amounts, status.total_claimed, status.claim_count));
+    }
+
+    /// Emitted when a distribution is claimed into a lock, including the lock bonus
+    ///
+    /// - topics - `["dist_lock", user: Address]`
+    /// - data - `[amounts: Vec<i128>, fees: Vec<i128>]`
+    pub fn claim_locked(e: &Env, user: Address, amounts: Vec<i128>, fees: Vec<i128>) {
+        let topics = (Symbol::new(e, "dist_lock"), user);
+        e.events().publish(topics, (amounts, fees));
+    }
+
+    /// Emitted when locked tokens are withdrawn
+    ///
+    /// - topics - `["dist_unlock", user: Address]`
+    /// - data - `amounts: Vec<i128>`
+    pub fn withdraw_lock(e: &Env, user: Address, amounts: Vec<i128>) {
+        let topics = (Symbol::new(e, "dist_unlock"), user);
+        e.events().publish(topics, amounts);
+    }
+
+    /// Emitted when a user is blocked by the admin
+    ///
+    /// - topics - `["dist_block", user: Address]`
+    /// - data - `()`
+    pub fn block(e: &Env, user: Address) {
+        let topics = (Symbol::new(e, "dist_block"), user);
+        e.events().publish(topics, ());
+    }
+
+    /// Emitted when a user is unblocked by the admin
+    ///
+    /// - topics - `["dist_unblock", user: Address]`
+    /// - data - `()`
+    pub fn unblock(e: &Env, user: Address) {
+        let topics = (Symbol::new(e, "dist_unblock"), user);
+        e.events().publish(topics, ());
+    }
+
+    /// Emitted when the contract is upgraded
+    ///
+    /// - topics - `["dist_upgrade"]`
+    /// - data - `wasm_hash: BytesN<32>`
+    pub fn upgrade(e: &Env, wasm_hash: BytesN<32>) {
+        let topics = (Symbol::new(e, "dist_upgrade"),);
+        e.events().publish(topics, wasm_hash);
+    }
+
+    /// Emitted when the admin renounces the ability to upgrade the contract
+    ///
+    /// - topics - `["dist_renounce"]`
+    /// - data - `()`
+    pub fn renounce_upgrade(e: &Env) {
+        let topics = (Symbol::new(e, "dist_renounce"),);
+        e.events().publish(topics, ());
+    }
+
+    /// Emitted when the storage layout is migrated
+    ///
+    /// - topics - `["dist_migrate"]`
+    /// - data - `[from_version: u32, to_version: u32]`
+    pub fn migrate(e: &Env, from_version: u32, to_version: u32) {
+        let topics = (Symbol::new(e, "dist_migrate"),);
+        e.events().publish(topics, (from_version, to_version));
+    }
+
+    /// Emitted when the Merkle root of allocations is set
+    ///
+    /// - topics - `["dist_merkle"]`
+    /// - data - `[root: BytesN<32>, totals: Vec<i128>]`
+    pub fn set_merkle_root(e: &Env, root: BytesN<32>, totals: Vec<i128>) {
+        let topics = (Symbol::new(e, "dist_merkle"),);
+        e.events().publish(topics, (root, totals));
     }
 }
@@ -1,17 +1,39 @@
 #![no_std]
 
-#[cfg(test)]
+#[cfg(any(test, feature = "std", feature = "testutils"))]
 extern crate std;
 
 mod contract;
 mod errors;
 mod events;
+mod receiver;
+mod registry;
 mod storage;
+mod types;
 
 pub use contract::*;
+pub use errors::ContractError;
+pub use receiver::*;
+pub use registry::*;
+pub use types::*;
+
+#[cfg(any(test, feature = "std"))]
+pub mod allocation;
+
+#[cfg(any(test, feature = "std"))]
+pub mod merkle;
+
+#[cfg(any(test, feature = "std"))]
+pub mod reconcile;
 
 #[cfg(test)]
 mod test;
 
+#[cfg(all(test, feature = "wasm-tests"))]
+mod cost_estimate;
+
 #[cfg(test)]
+mod test_properties;
+
+#[cfg(any(test, feature = "testutils"))]
 pub mod testutils;
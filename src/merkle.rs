@@ -0,0 +1,192 @@
+//! Off-chain builder for the Merkle tree of allocations claimed with `claim_merkle`.
+//!
+//! Leaves and nodes are hashed exactly as the contract verifies them:
+//! * leaf - `sha256(0x00 || xdr(ScVal::Address(user)) || amount_0 || .. || amount_n)`, with one
+//!   16 byte big-endian amount per token
+//! * node - `sha256(0x01 || a || b)`, where `a` and `b` are its children ordered by byte value
+//!
+//! A node without a sibling is carried up to the next layer unchanged, so proofs can be shorter
+//! than the height of the tree.
+
+use std::{
+    collections::HashSet,
+    fmt::Write as _,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use soroban_sdk::xdr::{Limits, ScAddress, ScVal, WriteXdr};
+
+pub type Hash = [u8; 32];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MerkleError {
+    /// No allocations were provided
+    Empty,
+    /// A user was allocated more than once
+    DuplicateUser(ScAddress),
+    /// A user's allocation does not have one amount per token, or has a negative amount
+    InvalidAmounts(ScAddress),
+    /// The total allocation of a token overflows
+    Overflow,
+}
+
+/// A Merkle tree of allocations, with one leaf per user in the order provided
+#[derive(Clone, Debug)]
+pub struct MerkleTree {
+    allocations: Vec<(ScAddress, Vec<i128>)>,
+    totals: Vec<i128>,
+    layers: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Build the tree from each user's allocation of every token
+    ///
+    /// ### Errors
+    /// If no allocations are provided, a user is allocated more than once, the allocations do not
+    /// all have the same number of amounts, an amount is negative, or a total overflows
+    pub fn new(allocations: Vec<(ScAddress, Vec<i128>)>) -> Result<Self, MerkleError> {
+        let token_count = allocations.first().ok_or(MerkleError::Empty)?.1.len();
+
+        let mut totals = vec![0_i128; token_count];
+        let mut leaves = Vec::with_capacity(allocations.len());
+        let mut users = HashSet::with_capacity(allocations.len());
+        for (user, amounts) in allocations.iter() {
+            if !users.insert(user) {
+                return Err(MerkleError::DuplicateUser(user.clone()));
+            }
+            if amounts.len() != token_count || amounts.iter().any(|amount| *amount < 0) {
+                return Err(MerkleError::InvalidAmounts(user.clone()));
+            }
+            for (total, amount) in totals.iter_mut().zip(amounts) {
+                *total = total.checked_add(*amount).ok_or(MerkleError::Overflow)?;
+            }
+            leaves.push(leaf_hash(user, amounts));
+        }
+
+        let mut layers = vec![leaves];
+        while layers[layers.len() - 1].len() > 1 {
+            let next = layers[layers.len() - 1]
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => node_hash(a, b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+            layers.push(next);
+        }
+
+        Ok(MerkleTree {
+            allocations,
+            totals,
+            layers,
+        })
+    }
+
+    /// The root of the tree, passed to `set_merkle_root`
+    pub fn root(&self) -> Hash {
+        self.layers[self.layers.len() - 1][0]
+    }
+
+    /// The sum of all allocations of each token, passed to `set_merkle_root`
+    pub fn totals(&self) -> &[i128] {
+        &self.totals
+    }
+
+    /// The allocations in the tree, in leaf order
+    pub fn allocations(&self) -> &[(ScAddress, Vec<i128>)] {
+        &self.allocations
+    }
+
+    /// The proof for the leaf at `index`, passed to `claim_merkle`
+    pub fn proof(&self, index: usize) -> Vec<Hash> {
+        let mut proof = Vec::new();
+        let mut index = index;
+        for layer in &self.layers[..self.layers.len() - 1] {
+            if let Some(sibling) = layer.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        proof
+    }
+
+    /// The proof for a user's leaf, if the user is in the tree
+    pub fn proof_for(&self, user: &ScAddress) -> Option<Vec<Hash>> {
+        let index = self
+            .allocations
+            .iter()
+            .position(|(other, _)| other == user)?;
+        Some(self.proof(index))
+    }
+
+    /// The root, totals, and each user's amounts and proof as JSON. Hashes are hex encoded and
+    /// amounts are decimal strings.
+    pub fn to_json(&self) -> Value {
+        let claims = self
+            .allocations
+            .iter()
+            .enumerate()
+            .map(|(index, (user, amounts))| {
+                json!({
+                    "address": user.to_string(),
+                    "amounts": amounts_json(amounts),
+                    "proof": self.proof(index).iter().map(to_hex).collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>();
+        json!({
+            "root": to_hex(&self.root()),
+            "totals": amounts_json(&self.totals),
+            "claims": claims,
+        })
+    }
+}
+
+/// Hash a user's allocation into a leaf
+pub fn leaf_hash(user: &ScAddress, amounts: &[i128]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([0]);
+    hasher.update(
+        ScVal::Address(user.clone())
+            .to_xdr(Limits::none())
+            .expect("address encodes to xdr"),
+    );
+    for amount in amounts {
+        hasher.update(amount.to_be_bytes());
+    }
+    hasher.finalize().into()
+}
+
+/// Hash two children into their parent node
+pub fn node_hash(a: &Hash, b: &Hash) -> Hash {
+    let (first, second) = if a <= b { (a, b) } else { (b, a) };
+    let mut hasher = Sha256::new();
+    hasher.update([1]);
+    hasher.update(first);
+    hasher.update(second);
+    hasher.finalize().into()
+}
+
+/// Check that a proof hashes from the leaf to the root
+pub fn verify_proof(root: &Hash, leaf: &Hash, proof: &[Hash]) -> bool {
+    let hash = proof
+        .iter()
+        .fold(*leaf, |hash, sibling| node_hash(&hash, sibling));
+    hash == *root
+}
+
+fn amounts_json(amounts: &[i128]) -> Vec<String> {
+    amounts.iter().map(|amount| amount.to_string()).collect()
+}
+
+fn to_hex(hash: &Hash) -> String {
+    hash.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02x}", byte);
+        hex
+    })
+}
@@ -0,0 +1,25 @@
+use soroban_sdk::{contractclient, Address, Env};
+
+/// The interface a contract must implement to receive distributions via `claim_to`
+#[contractclient(name = "DistributionReceiverClient")]
+pub trait DistributionReceiver {
+    /// Called after `amount` of `token` has been sent to the receiver on behalf of `user`
+    ///
+    /// Anyone can call this function directly, so receivers must call
+    /// `distributor.require_auth()` and check `distributor` is a distributor they trust before
+    /// crediting the deposit. The distributor is the direct caller, so its auth needs no
+    /// signature.
+    ///
+    /// ### Arguments
+    /// * `distributor` - The distributor that sent the tokens
+    /// * `user` - The user the distribution was claimed for
+    /// * `token` - The token that was sent
+    /// * `amount` - The amount of the token that was sent
+    fn on_distribution_received(
+        e: Env,
+        distributor: Address,
+        user: Address,
+        token: Address,
+        amount: i128,
+    );
+}
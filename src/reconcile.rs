@@ -0,0 +1,276 @@
+//! Off-chain reconciliation of a distribution against the events it emitted.
+//!
+//! Given the uploaded allocations, the amount of each token funded to the distributor, and the
+//! `dist_claim` and `dist_refund` events, the report lists what each user claimed and has left
+//! unclaimed, and flags any claim or balance that does not add up.
+//!
+//! Only liquid claims are reconciled. Distributions paid from a share pool, claimed into a lock,
+//! or claimed with a Merkle proof are not included in the allocations and will be flagged.
+
+use std::{vec, vec::Vec};
+
+use soroban_sdk::xdr::{ScAddress, ScVal};
+
+/// A `dist_claim` event
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ClaimEvent {
+    pub user: ScAddress,
+    /// The amount of each token sent to the user, after fees
+    pub amounts: Vec<i128>,
+    pub fees: Vec<i128>,
+}
+
+/// A `dist_refund` event
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefundEvent {
+    pub admin: ScAddress,
+    pub amounts: Vec<i128>,
+    /// The amount of each token claimed, including fees
+    pub total_claimed: Vec<i128>,
+    pub claim_count: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Claim(ClaimEvent),
+    Refund(RefundEvent),
+}
+
+impl Event {
+    /// Parse a distributor event from its topics and data. Returns `None` for events that are not
+    /// a `dist_claim` or `dist_refund` event.
+    pub fn parse(topics: &[ScVal], data: &ScVal) -> Option<Event> {
+        let (name, address) = match topics {
+            [ScVal::Symbol(name), ScVal::Address(address)] => {
+                (name.to_utf8_string_lossy(), address)
+            }
+            _ => return None,
+        };
+        let data = match data {
+            ScVal::Vec(Some(data)) => data.as_slice(),
+            _ => return None,
+        };
+        match (name.as_str(), data) {
+            ("dist_claim", [amounts, fees]) => Some(Event::Claim(ClaimEvent {
+                user: address.clone(),
+                amounts: parse_amounts(amounts)?,
+                fees: parse_amounts(fees)?,
+            })),
+            ("dist_refund", [amounts, total_claimed, ScVal::U32(claim_count)]) => {
+                Some(Event::Refund(RefundEvent {
+                    admin: address.clone(),
+                    amounts: parse_amounts(amounts)?,
+                    total_claimed: parse_amounts(total_claimed)?,
+                    claim_count: *claim_count,
+                }))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// A user claimed, including fees, a different amount than they were allocated
+    ClaimMismatch {
+        user: ScAddress,
+        allocated: Vec<i128>,
+        claimed: Vec<i128>,
+    },
+    /// A user claimed without an allocation
+    UnallocatedClaim { user: ScAddress },
+    /// A user claimed more than once
+    DoubleClaim { user: ScAddress },
+    /// The funding of a token does not cover its allocations
+    Underfunded {
+        token_index: u32,
+        allocated: i128,
+        funded: i128,
+    },
+    /// The total refunds of a token do not match the funding left after claims and fees
+    MissingFunds {
+        token_index: u32,
+        expected: i128,
+        refunded: i128,
+    },
+    /// The claim totals reported by a refund event do not match the claim events before it
+    RefundMismatch {
+        total_claimed: Vec<i128>,
+        claim_count: u32,
+    },
+}
+
+/// The claim progress of a single user
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UserReport {
+    pub user: ScAddress,
+    pub allocated: Vec<i128>,
+    /// The amount of each token sent to the user, after fees
+    pub claimed: Vec<i128>,
+    pub fees: Vec<i128>,
+    /// The amount of each token allocated but not claimed
+    pub unclaimed: Vec<i128>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// One entry per allocated user, in the order of the allocations
+    pub users: Vec<UserReport>,
+    pub total_allocated: Vec<i128>,
+    /// The amount of each token sent to users, after fees
+    pub total_claimed: Vec<i128>,
+    pub total_fees: Vec<i128>,
+    pub total_unclaimed: Vec<i128>,
+    pub claim_count: u32,
+    /// The amount of each token refunded across every refund event, if any were provided
+    pub refunded: Option<Vec<i128>>,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl Report {
+    /// Check if the distribution reconciles without any discrepancies
+    pub fn is_balanced(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+}
+
+/// Reconcile a distribution
+///
+/// ### Arguments
+/// * `allocations` - The allocation of each token for each user
+/// * `funded` - The amount of each token transferred to the distributor
+/// * `events` - The events emitted by the distributor, in order
+pub fn reconcile(
+    allocations: &[(ScAddress, Vec<i128>)],
+    funded: &[i128],
+    events: &[Event],
+) -> Report {
+    let token_count = funded.len();
+    let mut discrepancies = Vec::new();
+
+    let mut users: Vec<UserReport> = allocations
+        .iter()
+        .map(|(user, allocated)| UserReport {
+            user: user.clone(),
+            allocated: allocated.clone(),
+            claimed: vec![0; token_count],
+            fees: vec![0; token_count],
+            unclaimed: allocated.clone(),
+        })
+        .collect();
+    let mut claimers: Vec<&ScAddress> = Vec::new();
+    let mut total_claimed = vec![0; token_count];
+    let mut total_fees = vec![0; token_count];
+    let mut refunded: Option<Vec<i128>> = None;
+
+    for event in events {
+        match event {
+            Event::Claim(claim) => {
+                add(&mut total_claimed, &claim.amounts);
+                add(&mut total_fees, &claim.fees);
+                if claimers.contains(&&claim.user) {
+                    discrepancies.push(Discrepancy::DoubleClaim {
+                        user: claim.user.clone(),
+                    });
+                    continue;
+                }
+                claimers.push(&claim.user);
+
+                let Some(report) = users.iter_mut().find(|report| report.user == claim.user) else {
+                    discrepancies.push(Discrepancy::UnallocatedClaim {
+                        user: claim.user.clone(),
+                    });
+                    continue;
+                };
+                let mut gross = claim.amounts.clone();
+                add(&mut gross, &claim.fees);
+                if gross != report.allocated {
+                    discrepancies.push(Discrepancy::ClaimMismatch {
+                        user: claim.user.clone(),
+                        allocated: report.allocated.clone(),
+                        claimed: gross,
+                    });
+                }
+                report.claimed = claim.amounts.clone();
+                report.fees = claim.fees.clone();
+                report.unclaimed = vec![0; token_count];
+            }
+            Event::Refund(refund) => {
+                // each refund reports the claim progress at the time it was made
+                let mut gross_claimed = total_claimed.clone();
+                add(&mut gross_claimed, &total_fees);
+                if refund.total_claimed != gross_claimed
+                    || refund.claim_count as usize != claimers.len()
+                {
+                    discrepancies.push(Discrepancy::RefundMismatch {
+                        total_claimed: refund.total_claimed.clone(),
+                        claim_count: refund.claim_count,
+                    });
+                }
+                add(
+                    refunded.get_or_insert_with(|| vec![0; token_count]),
+                    &refund.amounts,
+                );
+            }
+        }
+    }
+
+    let mut total_allocated = vec![0; token_count];
+    let mut total_unclaimed = vec![0; token_count];
+    for report in &users {
+        add(&mut total_allocated, &report.allocated);
+        add(&mut total_unclaimed, &report.unclaimed);
+    }
+    for (index, (allocated, funded)) in total_allocated.iter().zip(funded).enumerate() {
+        if allocated > funded {
+            discrepancies.push(Discrepancy::Underfunded {
+                token_index: index as u32,
+                allocated: *allocated,
+                funded: *funded,
+            });
+        }
+    }
+
+    if let Some(refunded) = &refunded {
+        for (index, refunded) in refunded.iter().enumerate() {
+            let expected = funded[index] - total_claimed[index] - total_fees[index];
+            if expected != *refunded {
+                discrepancies.push(Discrepancy::MissingFunds {
+                    token_index: index as u32,
+                    expected,
+                    refunded: *refunded,
+                });
+            }
+        }
+    }
+
+    Report {
+        users,
+        total_allocated,
+        total_claimed,
+        total_fees,
+        total_unclaimed,
+        claim_count: claimers.len() as u32,
+        refunded,
+        discrepancies,
+    }
+}
+
+fn add(totals: &mut [i128], amounts: &[i128]) {
+    for (total, amount) in totals.iter_mut().zip(amounts) {
+        *total += amount;
+    }
+}
+
+fn parse_amounts(val: &ScVal) -> Option<Vec<i128>> {
+    match val {
+        ScVal::Vec(Some(amounts)) => amounts
+            .iter()
+            .map(|amount| match amount {
+                ScVal::I128(parts) => Some(((parts.hi as i128) << 64) | parts.lo as i128),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
@@ -0,0 +1,11 @@
+use soroban_sdk::{contractclient, Address, Env};
+
+/// The interface a registry contract must implement to gate claims with an allowlist
+#[contractclient(name = "AllowlistRegistryClient")]
+pub trait AllowlistRegistry {
+    /// Check if a user is allowed to claim their distribution
+    ///
+    /// ### Arguments
+    /// * `user` - The user claiming their distribution
+    fn is_allowed(e: Env, user: Address) -> bool;
+}
@@ -1,18 +1,40 @@
-use soroban_sdk::{contracttype, unwrap::UnwrapOptimized, Address, Env, Symbol};
+use soroban_sdk::{contracttype, unwrap::UnwrapOptimized, Address, BytesN, Env, Vec};
+
+use crate::types::{ClaimFee, DistributionStatus, LockBonus, MerkleRoot, SharePool};
 
 //********** Storage Keys **********//
 
-const IS_INIT_KEY: &str = "IsInit";
-const ADMIN_KEY: &str = "Admin";
-const TOKEN_KEY: &str = "Token";
-const DEADLINE_KEY: &str = "Deadline";
-const FINALIZED_KEY: &str = "Final";
+/// The version of the storage layout written by this contract
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Clone)]
+#[contracttype]
+pub enum InstanceKey {
+    SchemaVersion,
+    Admin,
+    Tokens,
+    Deadline,
+    Finalized,
+    Timelock,
+    AllocationHash,
+    RecipientCount,
+    Status,
+    Fee,
+    LockBonus,
+    Registry,
+    Pool,
+    UpgradeRenounced,
+    MerkleRoot,
+}
 
 #[derive(Clone)]
 #[contracttype]
 pub enum DistributorKey {
     Claim(Address),
     Dist(Address),
+    Recipients(u32),
+    Lock(Address),
+    Blocked(Address),
 }
 
 //********** Storage Utils **********//
@@ -22,7 +44,9 @@ pub const ONE_DAY_LEDGERS: u32 = 17280; // assumes 5 seconds per ledger on avera
 const LEDGER_BUMP_SHARED: u32 = 31 * ONE_DAY_LEDGERS;
 const LEDGER_THRESHOLD_SHARED: u32 = LEDGER_BUMP_SHARED - ONE_DAY_LEDGERS;
 
-const LEDGER_BUMP_MAX_DEADLINE: u32 = 91 * ONE_DAY_LEDGERS;
+const LEDGER_MARGIN_PAST_DEADLINE: u32 = ONE_DAY_LEDGERS;
+
+pub const RECIPIENTS_PAGE_SIZE: u32 = 100;
 
 /// Bump the instance lifetime by the defined amount
 pub fn extend_instance(e: &Env) {
@@ -33,35 +57,133 @@ pub fn extend_instance(e: &Env) {
 
 /********** Instance **********/
 
-/// Check if the contract has been initialized
-pub fn get_is_init(e: &Env) -> bool {
-    e.storage().instance().has(&Symbol::new(e, IS_INIT_KEY))
+/// Get the version of the storage layout, or 0 if the contract has not been initialized
+pub fn get_schema_version(e: &Env) -> u32 {
+    e.storage()
+        .instance()
+        .get(&InstanceKey::SchemaVersion)
+        .unwrap_or(0)
 }
 
-/// Set the contract as initialized
-pub fn set_is_init(e: &Env) {
+/// Set the version of the storage layout
+pub fn set_schema_version(e: &Env, version: &u32) {
     e.storage()
         .instance()
-        .set::<Symbol, bool>(&Symbol::new(e, IS_INIT_KEY), &true);
+        .set::<InstanceKey, u32>(&InstanceKey::SchemaVersion, version);
 }
 
-/// Check if the contract has been initialized
+/// Check if the distribution has been finalized
 pub fn is_finalized(e: &Env) -> bool {
-    e.storage().instance().has(&Symbol::new(e, FINALIZED_KEY))
+    e.storage().instance().has(&InstanceKey::Finalized)
+}
+
+/// Get the ledger sequence claims open at, if the distribution has been finalized
+pub fn get_unlock_ledger(e: &Env) -> Option<u32> {
+    e.storage().instance().get(&InstanceKey::Finalized)
+}
+
+/// Set the distribution as finalized, with claims opening at the given ledger sequence
+pub fn set_finalized(e: &Env, unlock_ledger: &u32) {
+    e.storage()
+        .instance()
+        .set::<InstanceKey, u32>(&InstanceKey::Finalized, unlock_ledger);
+}
+
+/// Get the fee taken from each claim, if any
+pub fn get_fee(e: &Env) -> Option<ClaimFee> {
+    e.storage().instance().get(&InstanceKey::Fee)
+}
+
+/// Set the fee taken from each claim
+pub fn set_fee(e: &Env, fee: &ClaimFee) {
+    e.storage()
+        .instance()
+        .set::<InstanceKey, ClaimFee>(&InstanceKey::Fee, fee);
+}
+
+/// Get the bonus for users who lock their distribution, if any
+pub fn get_lock_bonus(e: &Env) -> Option<LockBonus> {
+    e.storage().instance().get(&InstanceKey::LockBonus)
+}
+
+/// Set the bonus for users who lock their distribution
+pub fn set_lock_bonus(e: &Env, lock_bonus: &LockBonus) {
+    e.storage()
+        .instance()
+        .set::<InstanceKey, LockBonus>(&InstanceKey::LockBonus, lock_bonus);
+}
+
+/// Get the allowlist registry claims are checked against, if any
+pub fn get_registry(e: &Env) -> Option<Address> {
+    e.storage().instance().get(&InstanceKey::Registry)
+}
+
+/// Set the allowlist registry claims are checked against
+pub fn set_registry(e: &Env, registry: &Address) {
+    e.storage()
+        .instance()
+        .set::<InstanceKey, Address>(&InstanceKey::Registry, registry);
+}
+
+/// Get the share pool distributions are paid from, if any
+pub fn get_pool(e: &Env) -> Option<SharePool> {
+    e.storage().instance().get(&InstanceKey::Pool)
+}
+
+/// Set the share pool distributions are paid from
+pub fn set_pool(e: &Env, pool: &SharePool) {
+    e.storage()
+        .instance()
+        .set::<InstanceKey, SharePool>(&InstanceKey::Pool, pool);
+}
+
+/// Get the Merkle root of allocations claimable with a proof, if any
+pub fn get_merkle_root(e: &Env) -> Option<MerkleRoot> {
+    e.storage().instance().get(&InstanceKey::MerkleRoot)
+}
+
+/// Set the Merkle root of allocations claimable with a proof
+pub fn set_merkle_root(e: &Env, merkle_root: &MerkleRoot) {
+    e.storage()
+        .instance()
+        .set::<InstanceKey, MerkleRoot>(&InstanceKey::MerkleRoot, merkle_root);
 }
 
-/// Set the contract as initialized
-pub fn set_finalized(e: &Env) {
+/// Check if the admin has renounced the ability to upgrade the contract
+pub fn get_upgrade_renounced(e: &Env) -> bool {
     e.storage()
         .instance()
-        .set::<Symbol, bool>(&Symbol::new(e, FINALIZED_KEY), &true);
+        .get::<InstanceKey, bool>(&InstanceKey::UpgradeRenounced)
+        .unwrap_or(false)
+}
+
+/// Renounce the ability to upgrade the contract
+pub fn set_upgrade_renounced(e: &Env) {
+    e.storage()
+        .instance()
+        .set::<InstanceKey, bool>(&InstanceKey::UpgradeRenounced, &true);
+}
+
+/// Get the number of ledgers between finalization and claims opening
+pub fn get_timelock(e: &Env) -> u32 {
+    e.storage()
+        .instance()
+        .get(&InstanceKey::Timelock)
+        .unwrap_optimized()
+}
+
+/// Set the number of ledgers between finalization and claims opening
+pub fn set_timelock(e: &Env, ledgers: &u32) {
+    e.storage()
+        .instance()
+        .set::<InstanceKey, u32>(&InstanceKey::Timelock, ledgers);
 }
 
 /// Get the owner of the distribution
 pub fn get_admin(e: &Env) -> Address {
     e.storage()
         .instance()
-        .get(&Symbol::new(e, ADMIN_KEY))
+        .get(&InstanceKey::Admin)
         .unwrap_optimized()
 }
 
@@ -69,29 +191,29 @@ pub fn get_admin(e: &Env) -> Address {
 pub fn set_admin(e: &Env, admin: &Address) {
     e.storage()
         .instance()
-        .set::<Symbol, Address>(&Symbol::new(e, ADMIN_KEY), &admin);
+        .set::<InstanceKey, Address>(&InstanceKey::Admin, admin);
 }
 
-/// Get the token for distribution
-pub fn get_token(e: &Env) -> Address {
+/// Get the tokens for distribution
+pub fn get_tokens(e: &Env) -> Vec<Address> {
     e.storage()
         .instance()
-        .get(&Symbol::new(e, TOKEN_KEY))
+        .get(&InstanceKey::Tokens)
         .unwrap_optimized()
 }
 
-/// Set the token for distribution
-pub fn set_token(e: &Env, token: &Address) {
+/// Set the tokens for distribution
+pub fn set_tokens(e: &Env, tokens: &Vec<Address>) {
     e.storage()
         .instance()
-        .set::<Symbol, Address>(&Symbol::new(e, TOKEN_KEY), &token);
+        .set::<InstanceKey, Vec<Address>>(&InstanceKey::Tokens, tokens);
 }
 
 /// Get the deadline for distribution
 pub fn get_deadline(e: &Env) -> u32 {
     e.storage()
         .instance()
-        .get(&Symbol::new(e, DEADLINE_KEY))
+        .get(&InstanceKey::Deadline)
         .unwrap_optimized()
 }
 
@@ -99,39 +221,193 @@ pub fn get_deadline(e: &Env) -> u32 {
 pub fn set_deadline(e: &Env, ledger: &u32) {
     e.storage()
         .instance()
-        .set::<Symbol, u32>(&Symbol::new(e, DEADLINE_KEY), &ledger);
+        .set::<InstanceKey, u32>(&InstanceKey::Deadline, ledger);
+}
+
+/// Get the rolling hash of all allocations written
+pub fn get_allocation_hash(e: &Env) -> BytesN<32> {
+    e.storage()
+        .instance()
+        .get(&InstanceKey::AllocationHash)
+        .unwrap_or(BytesN::from_array(e, &[0; 32]))
+}
+
+/// Set the rolling hash of all allocations written
+pub fn set_allocation_hash(e: &Env, hash: &BytesN<32>) {
+    e.storage()
+        .instance()
+        .set::<InstanceKey, BytesN<32>>(&InstanceKey::AllocationHash, hash);
+}
+
+/// Get the number of recipients that have been set a distribution
+pub fn get_recipient_count(e: &Env) -> u32 {
+    e.storage()
+        .instance()
+        .get(&InstanceKey::RecipientCount)
+        .unwrap_or(0)
+}
+
+/// Set the number of recipients that have been set a distribution
+pub fn set_recipient_count(e: &Env, count: &u32) {
+    e.storage()
+        .instance()
+        .set::<InstanceKey, u32>(&InstanceKey::RecipientCount, count);
+}
+
+/// Get the aggregate progress of the distribution
+pub fn get_status(e: &Env) -> DistributionStatus {
+    e.storage()
+        .instance()
+        .get(&InstanceKey::Status)
+        .unwrap_optimized()
+}
+
+/// Set the aggregate progress of the distribution
+pub fn set_status(e: &Env, status: &DistributionStatus) {
+    e.storage()
+        .instance()
+        .set::<InstanceKey, DistributionStatus>(&InstanceKey::Status, status);
+}
+
+/********** Persistent **********/
+
+/// Get the amount of each token locked for a user, if any
+pub fn get_lock(e: &Env, user: &Address) -> Option<Vec<i128>> {
+    let key = DistributorKey::Lock(user.clone());
+    let result = e.storage().persistent().get(&key);
+    if result.is_some() {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, LEDGER_THRESHOLD_SHARED, LEDGER_BUMP_SHARED);
+    }
+    result
+}
+
+/// Set the amount of each token locked for a user. The lock is kept until the bump period past
+/// the release ledger, capped at the network's max TTL.
+pub fn set_lock(e: &Env, user: &Address, amounts: &Vec<i128>, release_ledger: u32) {
+    let key = DistributorKey::Lock(user.clone());
+    let ttl = (release_ledger.saturating_sub(e.ledger().sequence()) + LEDGER_BUMP_SHARED)
+        .min(e.storage().max_ttl());
+    e.storage()
+        .persistent()
+        .set::<DistributorKey, Vec<i128>>(&key, amounts);
+    e.storage().persistent().extend_ttl(&key, ttl, ttl);
+}
+
+/// Remove the lock for a user
+pub fn remove_lock(e: &Env, user: &Address) {
+    e.storage()
+        .persistent()
+        .remove(&DistributorKey::Lock(user.clone()));
+}
+
+/// Check if a user is blocked
+pub fn is_blocked(e: &Env, user: &Address) -> bool {
+    let key = DistributorKey::Blocked(user.clone());
+    let result = e.storage().persistent().has(&key);
+    if result {
+        e.storage()
+            .persistent()
+            .extend_ttl(&key, LEDGER_THRESHOLD_SHARED, LEDGER_BUMP_SHARED);
+    }
+    result
+}
+
+/// Block a user
+pub fn set_blocked(e: &Env, user: &Address) {
+    let key = DistributorKey::Blocked(user.clone());
+    e.storage()
+        .persistent()
+        .set::<DistributorKey, bool>(&key, &true);
+    e.storage()
+        .persistent()
+        .extend_ttl(&key, LEDGER_THRESHOLD_SHARED, LEDGER_BUMP_SHARED);
+}
+
+/// Unblock a user
+pub fn remove_blocked(e: &Env, user: &Address) {
+    e.storage()
+        .persistent()
+        .remove(&DistributorKey::Blocked(user.clone()));
 }
 
 /********** Temporary **********/
 
+/// Get the TTL that keeps a temporary entry written now until a margin past the deadline
+pub fn get_temporary_ttl(e: &Env) -> u32 {
+    get_deadline(e).saturating_sub(e.ledger().sequence()) + LEDGER_MARGIN_PAST_DEADLINE
+}
+
 /// Check if someone has claimed
 pub fn has_claimed(e: &Env, user: &Address) -> bool {
     let key = DistributorKey::Claim(user.clone());
     e.storage().temporary().has(&key)
 }
 
-/// Set that a user has claimed the distribution
+/// Set that a user has claimed the distribution. The claim flag is kept until a margin past
+/// the deadline, so it cannot expire while the distribution can still be claimed.
 pub fn set_claimed(e: &Env, user: &Address) {
     let key = DistributorKey::Claim(user.clone());
+    let ttl = get_temporary_ttl(e);
     e.storage()
         .temporary()
         .set::<DistributorKey, bool>(&key, &true);
-    e.storage()
-        .temporary()
-        .extend_ttl(&key, LEDGER_BUMP_MAX_DEADLINE, LEDGER_BUMP_MAX_DEADLINE);
+    e.storage().temporary().extend_ttl(&key, ttl, ttl);
 }
 
-/// Get the distribution for a user
-pub fn get_distribution(e: &Env, user: &Address) -> i128 {
+/// Get the distribution of each token for a user, if one has been set
+pub fn get_distribution(e: &Env, user: &Address) -> Option<Vec<i128>> {
     let key = DistributorKey::Dist(user.clone());
-    e.storage().temporary().get(&key).unwrap_or(0)
+    e.storage().temporary().get(&key)
 }
 
-/// Set the distribution for a user
-pub fn set_distribution(e: &Env, user: &Address, amount: i128) {
+/// Set the distribution of each token for a user
+pub fn set_distribution(e: &Env, user: &Address, amounts: &Vec<i128>) {
     let key = DistributorKey::Dist(user.clone());
-    e.storage().temporary().set(&key, &amount);
+    let ttl = get_temporary_ttl(e);
+    e.storage()
+        .temporary()
+        .set::<DistributorKey, Vec<i128>>(&key, amounts);
+    e.storage().temporary().extend_ttl(&key, ttl, ttl);
+}
+
+/// Get a page of recipients
+pub fn get_recipients(e: &Env, page: u32) -> Vec<Address> {
+    let key = DistributorKey::Recipients(page);
     e.storage()
         .temporary()
-        .extend_ttl(&key, LEDGER_BUMP_MAX_DEADLINE, LEDGER_BUMP_MAX_DEADLINE);
+        .get(&key)
+        .unwrap_or_else(|| Vec::new(e))
+}
+
+/// Set a page of recipients
+pub fn set_recipients(e: &Env, page: u32, recipients: &Vec<Address>) {
+    let key = DistributorKey::Recipients(page);
+    let ttl = get_temporary_ttl(e);
+    e.storage()
+        .temporary()
+        .set::<DistributorKey, Vec<Address>>(&key, recipients);
+    e.storage().temporary().extend_ttl(&key, ttl, ttl);
+}
+
+/// Append new recipients to the end of the recipient list
+pub fn add_recipients(e: &Env, new_recipients: &Vec<Address>) {
+    if new_recipients.is_empty() {
+        return;
+    }
+    let mut count = get_recipient_count(e);
+    let mut page = count / RECIPIENTS_PAGE_SIZE;
+    let mut recipients = get_recipients(e, page);
+    for recipient in new_recipients.iter() {
+        if recipients.len() == RECIPIENTS_PAGE_SIZE {
+            set_recipients(e, page, &recipients);
+            page += 1;
+            recipients = Vec::new(e);
+        }
+        recipients.push_back(recipient);
+        count += 1;
+    }
+    set_recipients(e, page, &recipients);
+    set_recipient_count(e, &count);
 }
@@ -1,27 +1,126 @@
-#![cfg(test)]
-
 use crate::{
-    errors::ContractError, storage::ONE_DAY_LEDGERS, testutils::EnvTestUtils, DistributorClient,
+    allocation,
+    errors::ContractError,
+    merkle, reconcile,
+    storage::{DistributorKey, ONE_DAY_LEDGERS, SCHEMA_VERSION},
+    testutils::{create_distributor, EnvTestUtils},
+    ClaimFee, DistributionStatus, DistributorClient, DistributorOption, LockBonus, SharePool,
 };
 use soroban_sdk::{
-    testutils::{Address as _, AuthorizedFunction, AuthorizedInvocation, Events},
+    testutils::{Address as _, AuthorizedFunction, AuthorizedInvocation, Events, Ledger as _},
     token::{StellarAssetClient, TokenClient},
-    vec, Address, Env, Error, IntoVal, Symbol,
+    vec,
+    xdr::{ScAddress, ScVal},
+    Address, BytesN, Env, Error, IntoVal, Symbol, TryFromVal,
 };
 
-mod distributor_wasm {
+#[cfg(feature = "wasm-tests")]
+pub(crate) mod distributor_wasm {
     soroban_sdk::contractimport!(
         file = "./target/wasm32-unknown-unknown/optimized/basic_distributor.wasm"
     );
 }
 
+#[cfg(feature = "wasm-tests")]
+mod upgrade_wasm {
+    soroban_sdk::contractimport!(
+        file = "./target/wasm32-unknown-unknown/optimized/upgrade_test_contract.wasm"
+    );
+}
+
+/// Register the distributor from the optimized wasm
+#[cfg(feature = "wasm-tests")]
+pub(crate) fn register_distributor(env: &Env) -> Address {
+    env.register_contract_wasm(None, distributor_wasm::WASM)
+}
+
+/// Register the native distributor, so tests run without building the wasm first
+#[cfg(not(feature = "wasm-tests"))]
+pub(crate) fn register_distributor(env: &Env) -> Address {
+    env.register_contract(None, crate::Distributor)
+}
+
+mod mock_receiver {
+    use soroban_sdk::{contract, contractimpl, token::TokenClient, Address, Env, Symbol, Vec};
+
+    /// A receiver that records each distribution it is notified of by its trusted distributor
+    #[contract]
+    pub struct MockReceiver;
+
+    #[contractimpl]
+    impl MockReceiver {
+        pub fn set_distributor(e: Env, distributor: Address) {
+            e.storage()
+                .instance()
+                .set(&Symbol::new(&e, "Distributor"), &distributor);
+        }
+
+        pub fn on_distribution_received(
+            e: Env,
+            distributor: Address,
+            user: Address,
+            token: Address,
+            amount: i128,
+        ) {
+            // only the trusted distributor can report deposits
+            let trusted: Address = e
+                .storage()
+                .instance()
+                .get(&Symbol::new(&e, "Distributor"))
+                .unwrap();
+            assert_eq!(distributor, trusted);
+            distributor.require_auth();
+
+            // the tokens must be sent before the receiver is notified
+            let balance = TokenClient::new(&e, &token).balance(&e.current_contract_address());
+            assert!(balance >= amount);
+
+            let key = Symbol::new(&e, "Received");
+            let mut received: Vec<(Address, Address, i128)> = e
+                .storage()
+                .instance()
+                .get(&key)
+                .unwrap_or_else(|| Vec::new(&e));
+            received.push_back((user, token, amount));
+            e.storage().instance().set(&key, &received);
+        }
+
+        pub fn get_received(e: Env) -> Vec<(Address, Address, i128)> {
+            e.storage()
+                .instance()
+                .get(&Symbol::new(&e, "Received"))
+                .unwrap_or_else(|| Vec::new(&e))
+        }
+    }
+}
+
+mod mock_registry {
+    use soroban_sdk::{contract, contractimpl, Address, Env};
+
+    /// An allowlist registry where each user's allowed flag is set directly
+    #[contract]
+    pub struct MockRegistry;
+
+    #[contractimpl]
+    impl MockRegistry {
+        pub fn set_allowed(e: Env, user: Address, allowed: bool) {
+            e.storage().instance().set(&user, &allowed);
+        }
+
+        pub fn is_allowed(e: Env, user: Address) -> bool {
+            e.storage().instance().get(&user).unwrap_or(false)
+        }
+    }
+}
+
 #[test]
 fn test_distribute() {
     let env = Env::default();
     env.set_default_info();
     env.mock_all_auths();
+    env.budget().reset_unlimited();
 
-    let dist_id = env.register_contract_wasm(None, distributor_wasm::WASM);
+    let dist_id = register_distributor(&env);
     let dist_client = DistributorClient::new(&env, &dist_id);
 
     let token_admin = Address::generate(&env);
@@ -46,16 +145,26 @@ fn test_distribute() {
     let total_amount = amount1 + amount2 + amount3 + amount4 + amount5;
     token_setup_client.mint(&dist_id, &total_amount);
 
-    dist_client.initialize(&token, &deadline, &admin);
+    dist_client.initialize(&vec![&env, token.clone()], &deadline, &admin, &vec![&env]);
 
     dist_client.set_distribution(&vec![
         &env,
-        (addr1.clone(), amount1),
-        (addr2.clone(), amount2),
-        (addr3.clone(), amount3),
-        (addr4_no_claim.clone(), amount4),
+        (addr1.clone(), vec![&env, (0, amount1)]),
+        (addr2.clone(), vec![&env, (0, amount2)]),
+        (addr3.clone(), vec![&env, (0, amount3)]),
+        (addr4_no_claim.clone(), vec![&env, (0, amount4)]),
     ]);
-    dist_client.set_distribution(&vec![&env, (addr5.clone(), amount5)]);
+    dist_client.set_distribution(&vec![&env, (addr5.clone(), vec![&env, (0, amount5)])]);
+
+    // verify negative distributions cannot be set
+    let result =
+        dist_client.try_set_distribution(&vec![&env, (addr6_no_dist.clone(), vec![&env, (0, -1)])]);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::NegativeAmountError as u32
+        )))
+    );
 
     env.jump(ONE_DAY_LEDGERS);
 
@@ -68,10 +177,10 @@ fn test_distribute() {
         )))
     );
 
-    dist_client.finalize();
+    dist_client.finalize(&None, &None);
 
     // verify finalize and set_distribution cannot be called again
-    let result = dist_client.try_finalize();
+    let result = dist_client.try_finalize(&None, &None);
     assert_eq!(
         result.err(),
         Some(Ok(Error::from_contract_error(
@@ -79,7 +188,8 @@ fn test_distribute() {
         )))
     );
 
-    let result = dist_client.try_set_distribution(&vec![&env, (addr6_no_dist.clone(), 1)]);
+    let result =
+        dist_client.try_set_distribution(&vec![&env, (addr6_no_dist.clone(), vec![&env, (0, 1)])]);
     assert_eq!(
         result.err(),
         Some(Ok(Error::from_contract_error(
@@ -89,7 +199,7 @@ fn test_distribute() {
 
     // verify claim
     let claim_amount_1 = dist_client.claim(&addr1);
-    assert_eq!(claim_amount_1, amount1);
+    assert_eq!(claim_amount_1, vec![&env, amount1]);
 
     // claim - validate auth
     assert_eq!(
@@ -117,7 +227,7 @@ fn test_distribute() {
             (
                 dist_id.clone(),
                 (Symbol::new(&env, "dist_claim"), addr1.clone()).into_val(&env),
-                claim_amount_1.into_val(&env)
+                (claim_amount_1.clone(), vec![&env, 0_i128]).into_val(&env)
             )
         ]
     );
@@ -125,8 +235,18 @@ fn test_distribute() {
     // claim - validate chain results
     assert_eq!(token_client.balance(&addr1), amount1);
     assert_eq!(token_client.balance(&dist_id), total_amount - amount1);
-    assert_eq!(dist_client.get_claimed(&addr1), true);
-    assert_eq!(dist_client.get_claimed(&addr2), false);
+    assert!(dist_client.get_claimed(&addr1));
+    assert!(!dist_client.get_claimed(&addr2));
+    assert_eq!(
+        dist_client.get_status(),
+        DistributionStatus {
+            total_allocated: vec![&env, total_amount],
+            total_claimed: vec![&env, amount1],
+            total_fees: vec![&env, 0],
+            total_locked: vec![&env, 0],
+            claim_count: 1,
+        }
+    );
 
     env.jump(89 * ONE_DAY_LEDGERS);
 
@@ -181,20 +301,50 @@ fn test_distribute() {
     // refund - verify auth
     assert_eq!(env.auths().len(), 0);
 
+    // refund - validate events
+    let events = env.events().all();
+    let tx_events = vec![&env, events.last().unwrap()];
+    assert_eq!(
+        tx_events,
+        vec![
+            &env,
+            (
+                dist_id.clone(),
+                (Symbol::new(&env, "dist_refund"), admin.clone()).into_val(&env),
+                (
+                    refund_amount.clone(),
+                    vec![&env, total_amount - amount4],
+                    4u32
+                )
+                    .into_val(&env)
+            )
+        ]
+    );
+    assert_eq!(
+        dist_client.get_status(),
+        DistributionStatus {
+            total_allocated: vec![&env, total_amount],
+            total_claimed: vec![&env, total_amount - amount4],
+            total_fees: vec![&env, 0],
+            total_locked: vec![&env, 0],
+            claim_count: 4,
+        }
+    );
+
     // ***** verify tokens are correctly distributed *****
 
     assert_eq!(token_client.balance(&addr1), amount1);
-    assert_eq!(amount1, claim_amount_1);
+    assert_eq!(vec![&env, amount1], claim_amount_1);
     assert_eq!(token_client.balance(&addr2), amount2);
-    assert_eq!(amount2, claim_amount_2);
+    assert_eq!(vec![&env, amount2], claim_amount_2);
     assert_eq!(token_client.balance(&addr3), amount3);
-    assert_eq!(amount3, claim_amount_3);
+    assert_eq!(vec![&env, amount3], claim_amount_3);
     assert_eq!(token_client.balance(&addr4_no_claim), 0);
     assert_eq!(token_client.balance(&addr5), amount5);
-    assert_eq!(amount5, claim_amount_5);
+    assert_eq!(vec![&env, amount5], claim_amount_5);
     assert_eq!(token_client.balance(&dist_id), 0);
-    assert_eq!(token_client.balance(&admin), refund_amount);
-    assert_eq!(refund_amount, amount4);
+    assert_eq!(refund_amount, vec![&env, amount4]);
+    assert_eq!(token_client.balance(&admin), amount4);
 }
 
 #[test]
@@ -202,8 +352,9 @@ fn test_valid_initialize() {
     let env = Env::default();
     env.set_default_info();
     env.mock_all_auths();
+    env.budget().reset_unlimited();
 
-    let dist_id = env.register_contract_wasm(None, distributor_wasm::WASM);
+    let dist_id = register_distributor(&env);
     let dist_client = DistributorClient::new(&env, &dist_id);
 
     let token_admin = Address::generate(&env);
@@ -211,7 +362,12 @@ fn test_valid_initialize() {
     let admin = Address::generate(&env);
 
     let low_deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS - 1;
-    let result_low = dist_client.try_initialize(&token, &low_deadline, &admin);
+    let result_low = dist_client.try_initialize(
+        &vec![&env, token.clone()],
+        &low_deadline,
+        &admin,
+        &vec![&env],
+    );
     assert_eq!(
         result_low.err(),
         Some(Ok(Error::from_contract_error(
@@ -220,7 +376,12 @@ fn test_valid_initialize() {
     );
 
     let high_deadline = env.ledger().sequence() + 90 * ONE_DAY_LEDGERS + 1;
-    let result_high = dist_client.try_initialize(&token, &high_deadline, &admin);
+    let result_high = dist_client.try_initialize(
+        &vec![&env, token.clone()],
+        &high_deadline,
+        &admin,
+        &vec![&env],
+    );
     assert_eq!(
         result_high.err(),
         Some(Ok(Error::from_contract_error(
@@ -228,11 +389,57 @@ fn test_valid_initialize() {
         )))
     );
 
-    dist_client.initialize(&token, &(low_deadline + 1), &admin);
+    let result_no_tokens =
+        dist_client.try_initialize(&vec![&env], &(low_deadline + 1), &admin, &vec![&env]);
+    assert_eq!(
+        result_no_tokens.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::InvalidTokenError as u32
+        )))
+    );
+
+    let result_dup_tokens = dist_client.try_initialize(
+        &vec![&env, token.clone(), token.clone()],
+        &(low_deadline + 1),
+        &admin,
+        &vec![&env],
+    );
+    assert_eq!(
+        result_dup_tokens.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::InvalidTokenError as u32
+        )))
+    );
+
+    let result_dup_options = dist_client.try_initialize(
+        &vec![&env, token.clone()],
+        &(low_deadline + 1),
+        &admin,
+        &vec![
+            &env,
+            DistributorOption::Timelock(1),
+            DistributorOption::Timelock(2),
+        ],
+    );
+    assert_eq!(
+        result_dup_options.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::OptionError as u32
+        )))
+    );
+
+    dist_client.initialize(
+        &vec![&env, token.clone()],
+        &(low_deadline + 1),
+        &admin,
+        &vec![&env],
+    );
 
     assert_eq!(dist_client.get_admin(), admin);
     assert_eq!(dist_client.get_deadline(), low_deadline + 1);
-    assert_eq!(dist_client.get_token(), token);
+    assert_eq!(dist_client.get_tokens(), vec![&env, token.clone()]);
+    assert_eq!(dist_client.get_timelock(), 0);
+    assert_eq!(dist_client.get_fee(), None);
 }
 
 #[test]
@@ -240,8 +447,9 @@ fn test_admin_only_functions() {
     let env = Env::default();
     env.set_default_info();
     env.mock_all_auths();
+    env.budget().reset_unlimited();
 
-    let dist_id = env.register_contract_wasm(None, distributor_wasm::WASM);
+    let dist_id = register_distributor(&env);
     let dist_client = DistributorClient::new(&env, &dist_id);
 
     let token_admin = Address::generate(&env);
@@ -251,12 +459,12 @@ fn test_admin_only_functions() {
     let admin = Address::generate(&env);
     let deadline = env.ledger().sequence() + 45 * ONE_DAY_LEDGERS;
 
-    dist_client.initialize(&token, &deadline, &admin);
+    dist_client.initialize(&vec![&env, token.clone()], &deadline, &admin, &vec![&env]);
 
     let amount = 123145;
     token_setup_client.mint(&dist_id, &amount);
     let addr1 = Address::generate(&env);
-    let distributions = vec![&env, (addr1.clone(), amount)];
+    let distributions = vec![&env, (addr1.clone(), vec![&env, (0, amount)])];
 
     dist_client.set_distribution(&distributions);
 
@@ -276,7 +484,7 @@ fn test_admin_only_functions() {
         )
     );
 
-    dist_client.finalize();
+    dist_client.finalize(&None, &None);
 
     // validate auth
     assert_eq!(
@@ -287,7 +495,7 @@ fn test_admin_only_functions() {
                 function: AuthorizedFunction::Contract((
                     dist_id.clone(),
                     Symbol::new(&env, "finalize"),
-                    vec![&env]
+                    vec![&env, ().into_val(&env), ().into_val(&env)]
                 )),
                 sub_invocations: std::vec![]
             }
@@ -320,7 +528,2095 @@ fn test_admin_only_functions() {
 
     // validate refund goes to current admin
     let refund_amount = dist_client.refund();
-    assert_eq!(token_client.balance(&new_admin), refund_amount);
+    assert_eq!(refund_amount, vec![&env, amount]);
+    assert_eq!(token_client.balance(&new_admin), amount);
     assert_eq!(token_client.balance(&admin), 0);
-    assert_eq!(refund_amount, amount);
+}
+
+#[test]
+fn test_revoke() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let token_setup_client = StellarAssetClient::new(&env, &token);
+    let token_client = TokenClient::new(&env, &token);
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+
+    let addr1 = Address::generate(&env);
+    let amount1: i128 = 5423423;
+    let addr2_revoked = Address::generate(&env);
+    let amount2: i128 = 123412341;
+    let addr3_no_dist = Address::generate(&env);
+
+    token_setup_client.mint(&dist_id, &(amount1 + amount2));
+
+    dist_client.initialize(&vec![&env, token.clone()], &deadline, &admin, &vec![&env]);
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (0, amount1)]),
+        (addr2_revoked.clone(), vec![&env, (0, amount2)]),
+    ]);
+    dist_client.finalize(&None, &None);
+
+    dist_client.claim(&addr1);
+
+    // verify claimed distributions cannot be revoked
+    let result = dist_client.try_revoke(&vec![&env, addr1.clone()]);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::AlreadyClaimedError as u32
+        )))
+    );
+
+    // verify users without a distribution cannot be revoked
+    let result = dist_client.try_revoke(&vec![&env, addr3_no_dist.clone()]);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::NoDistributionError as u32
+        )))
+    );
+
+    let users = vec![&env, addr2_revoked.clone()];
+    dist_client.revoke(&users);
+
+    // revoke - validate auth
+    assert_eq!(
+        env.auths()[0],
+        (
+            admin.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    dist_id.clone(),
+                    Symbol::new(&env, "revoke"),
+                    vec![&env, users.to_val()]
+                )),
+                sub_invocations: std::vec![]
+            }
+        )
+    );
+
+    // revoke - validate events
+    let events = env.events().all();
+    let tx_events = vec![&env, events.last().unwrap()];
+    assert_eq!(
+        tx_events,
+        vec![
+            &env,
+            (
+                dist_id.clone(),
+                (Symbol::new(&env, "dist_revoke"), addr2_revoked.clone()).into_val(&env),
+                vec![&env, amount2].into_val(&env)
+            )
+        ]
+    );
+
+    assert_eq!(
+        dist_client.get_status(),
+        DistributionStatus {
+            total_allocated: vec![&env, amount1],
+            total_claimed: vec![&env, amount1],
+            total_fees: vec![&env, 0],
+            total_locked: vec![&env, 0],
+            claim_count: 1,
+        }
+    );
+
+    // verify revoked user cannot claim
+    let result = dist_client.try_claim(&addr2_revoked);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::NoDistributionError as u32
+        )))
+    );
+
+    // verify revoked distribution is refunded to the admin
+    env.jump(30 * ONE_DAY_LEDGERS + 1);
+
+    let refund_amount = dist_client.refund();
+    assert_eq!(refund_amount, vec![&env, amount2]);
+    assert_eq!(token_client.balance(&admin), amount2);
+    assert_eq!(token_client.balance(&addr2_revoked), 0);
+    assert_eq!(token_client.balance(&addr1), amount1);
+}
+
+#[test]
+fn test_finalize_timelock() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let token_setup_client = StellarAssetClient::new(&env, &token);
+    let token_client = TokenClient::new(&env, &token);
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+    let timelock = 7 * ONE_DAY_LEDGERS;
+
+    let addr1 = Address::generate(&env);
+    let amount1: i128 = 9876543;
+    let amount1_corrected: i128 = 1234567;
+
+    token_setup_client.mint(&dist_id, &amount1);
+
+    dist_client.initialize(
+        &vec![&env, token.clone()],
+        &deadline,
+        &admin,
+        &vec![&env, DistributorOption::Timelock(timelock)],
+    );
+    assert_eq!(dist_client.get_timelock(), timelock);
+    assert_eq!(dist_client.get_unlock_ledger(), None);
+
+    dist_client.set_distribution(&vec![&env, (addr1.clone(), vec![&env, (0, amount1)])]);
+
+    // verify the timelock must elapse before the deadline
+    env.jump(23 * ONE_DAY_LEDGERS + 1);
+    let result = dist_client.try_finalize(&None, &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::DeadlineError as u32
+        )))
+    );
+
+    // reset the ledger to the start of the distribution
+    env.set_default_info();
+
+    dist_client.finalize(&None, &None);
+    let unlock_ledger = env.ledger().sequence() + timelock;
+    assert_eq!(dist_client.get_unlock_ledger(), Some(unlock_ledger));
+
+    // finalize - validate events
+    let events = env.events().all();
+    let tx_events = vec![&env, events.last().unwrap()];
+    assert_eq!(
+        tx_events,
+        vec![
+            &env,
+            (
+                dist_id.clone(),
+                (Symbol::new(&env, "dist_final"),).into_val(&env),
+                unlock_ledger.into_val(&env)
+            )
+        ]
+    );
+
+    // verify claim is blocked during the timelock
+    let result = dist_client.try_claim(&addr1);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::TimelockError as u32
+        )))
+    );
+
+    // verify distribution can be corrected during the timelock
+    env.jump(timelock - 1);
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (0, amount1_corrected)]),
+    ]);
+
+    // set_distribution - validate events
+    let events = env.events().all();
+    let tx_events = vec![&env, events.last().unwrap()];
+    assert_eq!(
+        tx_events,
+        vec![
+            &env,
+            (
+                dist_id.clone(),
+                (Symbol::new(&env, "dist_set"), addr1.clone()).into_val(&env),
+                vec![&env, amount1_corrected].into_val(&env)
+            )
+        ]
+    );
+
+    let result = dist_client.try_claim(&addr1);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::TimelockError as u32
+        )))
+    );
+
+    env.jump(1);
+
+    // verify distribution is locked once the timelock elapses
+    let result =
+        dist_client.try_set_distribution(&vec![&env, (addr1.clone(), vec![&env, (0, amount1)])]);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::AlreadyFinalizedError as u32
+        )))
+    );
+
+    let claim_amount = dist_client.claim(&addr1);
+    assert_eq!(claim_amount, vec![&env, amount1_corrected]);
+    assert_eq!(token_client.balance(&addr1), amount1_corrected);
+    assert_eq!(token_client.balance(&dist_id), amount1 - amount1_corrected);
+
+    // verify a timelock that overflows the ledger sequence cannot be finalized
+    let dist_client = DistributorClient::new(&env, &register_distributor(&env));
+    dist_client.initialize(
+        &vec![&env, token.clone()],
+        &(env.ledger().sequence() + 30 * ONE_DAY_LEDGERS),
+        &admin,
+        &vec![&env, DistributorOption::Timelock(u32::MAX)],
+    );
+    let result = dist_client.try_finalize(&None, &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::DeadlineError as u32
+        )))
+    );
+}
+
+#[test]
+fn test_allocation_hash() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+
+    let addr1 = Address::generate(&env);
+    let amount1: i128 = 1342345;
+    let addr2 = Address::generate(&env);
+    let amount2: i128 = 89657234523425;
+    let addr3 = Address::generate(&env);
+    let amount3: i128 = 7823412341;
+
+    // reproduce the hash from the published list of writes
+    let roll = |hash: BytesN<32>, user: &Address, amounts: &[i128]| -> BytesN<32> {
+        let user = ScAddress::try_from(user).unwrap();
+        BytesN::from_array(
+            &env,
+            &allocation::roll_hash(&hash.to_array(), &user, amounts),
+        )
+    };
+    let empty_hash = BytesN::from_array(&env, &allocation::EMPTY_HASH);
+    let hash_1 = roll(empty_hash.clone(), &addr1, &[amount1]);
+    let hash_2 = roll(hash_1.clone(), &addr2, &[amount2]);
+    let hash_3 = roll(hash_2.clone(), &addr3, &[amount3]);
+    let hash_revoke = roll(hash_3.clone(), &addr2, &[0]);
+    let writes = std::vec![
+        (ScAddress::try_from(&addr1).unwrap(), std::vec![amount1]),
+        (ScAddress::try_from(&addr2).unwrap(), std::vec![amount2]),
+        (ScAddress::try_from(&addr3).unwrap(), std::vec![amount3]),
+        (ScAddress::try_from(&addr2).unwrap(), std::vec![0]),
+    ];
+    assert_eq!(allocation::allocation_hash(&writes), hash_revoke.to_array());
+
+    dist_client.initialize(&vec![&env, token.clone()], &deadline, &admin, &vec![&env]);
+    assert_eq!(dist_client.get_allocation_hash(), empty_hash);
+
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (0, amount1)]),
+        (addr2.clone(), vec![&env, (0, amount2)]),
+    ]);
+    assert_eq!(dist_client.get_allocation_hash(), hash_2);
+
+    dist_client.set_distribution(&vec![&env, (addr3.clone(), vec![&env, (0, amount3)])]);
+    assert_eq!(dist_client.get_allocation_hash(), hash_3);
+
+    dist_client.revoke(&vec![&env, addr2.clone()]);
+    assert_eq!(dist_client.get_allocation_hash(), hash_revoke);
+
+    // verify finalize fails if the allocation hash does not match
+    let result = dist_client.try_finalize(&Some(hash_3), &None);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::AllocationHashError as u32
+        )))
+    );
+
+    dist_client.finalize(&Some(hash_revoke.clone()), &None);
+    assert_eq!(
+        dist_client.get_unlock_ledger(),
+        Some(env.ledger().sequence())
+    );
+    assert_eq!(dist_client.get_allocation_hash(), hash_revoke);
+}
+
+#[test]
+fn test_get_recipients() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let token_setup_client = StellarAssetClient::new(&env, &token);
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+
+    dist_client.initialize(&vec![&env, token.clone()], &deadline, &admin, &vec![&env]);
+    assert_eq!(dist_client.get_recipient_count(), 0);
+    assert_eq!(dist_client.get_recipients(&0), vec![&env]);
+
+    // upload 150 recipients over two batches, with the second batch overwriting a recipient
+    let mut users = std::vec::Vec::new();
+    let mut batch_1 = vec![&env];
+    for i in 0..120 {
+        let user = Address::generate(&env);
+        batch_1.push_back((user.clone(), vec![&env, (0, 1000 + i as i128)]));
+        users.push(user);
+    }
+    dist_client.set_distribution(&batch_1);
+
+    let mut batch_2 = vec![&env, (users[5].clone(), vec![&env, (0, 5)])];
+    for i in 120..150 {
+        let user = Address::generate(&env);
+        batch_2.push_back((user.clone(), vec![&env, (0, 1000 + i as i128)]));
+        users.push(user);
+    }
+    dist_client.set_distribution(&batch_2);
+
+    token_setup_client.mint(&dist_id, &1_000_000);
+    dist_client.finalize(&None, &None);
+    dist_client.claim(&users[101]);
+
+    assert_eq!(dist_client.get_recipient_count(), 150);
+
+    let page_0 = dist_client.get_recipients(&0);
+    assert_eq!(page_0.len(), 100);
+    assert_eq!(
+        page_0.get_unchecked(0),
+        (users[0].clone(), vec![&env, 1000], false)
+    );
+    assert_eq!(
+        page_0.get_unchecked(5),
+        (users[5].clone(), vec![&env, 5], false)
+    );
+    assert_eq!(
+        page_0.get_unchecked(99),
+        (users[99].clone(), vec![&env, 1099], false)
+    );
+
+    let page_1 = dist_client.get_recipients(&1);
+    assert_eq!(page_1.len(), 50);
+    assert_eq!(
+        page_1.get_unchecked(0),
+        (users[100].clone(), vec![&env, 1100], false)
+    );
+    assert_eq!(
+        page_1.get_unchecked(1),
+        (users[101].clone(), vec![&env, 1101], true)
+    );
+    assert_eq!(
+        page_1.get_unchecked(49),
+        (users[149].clone(), vec![&env, 1149], false)
+    );
+
+    assert_eq!(dist_client.get_recipients(&2), vec![&env]);
+}
+
+#[test]
+fn test_multi_token() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token_0 = env.register_stellar_asset_contract(token_admin.clone());
+    let token_0_setup_client = StellarAssetClient::new(&env, &token_0);
+    let token_0_client = TokenClient::new(&env, &token_0);
+    let token_1 = env.register_stellar_asset_contract(token_admin.clone());
+    let token_1_setup_client = StellarAssetClient::new(&env, &token_1);
+    let token_1_client = TokenClient::new(&env, &token_1);
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+
+    let addr1 = Address::generate(&env);
+    let amount1_0: i128 = 1342345;
+    let amount1_1: i128 = 56734;
+    let addr2_token_1_only = Address::generate(&env);
+    let amount2_1: i128 = 2345234;
+    let addr3_no_claim = Address::generate(&env);
+    let amount3_0: i128 = 8765;
+    let amount3_1: i128 = 4321;
+
+    token_0_setup_client.mint(&dist_id, &(amount1_0 + amount3_0));
+    token_1_setup_client.mint(&dist_id, &(amount1_1 + amount2_1 + amount3_1));
+
+    dist_client.initialize(
+        &vec![&env, token_0.clone(), token_1.clone()],
+        &deadline,
+        &admin,
+        &vec![&env],
+    );
+    assert_eq!(
+        dist_client.get_tokens(),
+        vec![&env, token_0.clone(), token_1.clone()]
+    );
+
+    // verify token indexes must be registered
+    let result = dist_client.try_set_distribution(&vec![&env, (addr1.clone(), vec![&env, (2, 1)])]);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::InvalidTokenError as u32
+        )))
+    );
+
+    // verify token indexes cannot repeat for a user
+    let result =
+        dist_client.try_set_distribution(&vec![&env, (addr1.clone(), vec![&env, (0, 1), (0, 2)])]);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::InvalidTokenError as u32
+        )))
+    );
+
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (1, amount1_1), (0, amount1_0)]),
+        (addr2_token_1_only.clone(), vec![&env, (1, amount2_1)]),
+        (
+            addr3_no_claim.clone(),
+            vec![&env, (0, amount3_0), (1, amount3_1)],
+        ),
+    ]);
+    dist_client.finalize(&None, &None);
+
+    assert_eq!(
+        dist_client.get_recipients(&0),
+        vec![
+            &env,
+            (addr1.clone(), vec![&env, amount1_0, amount1_1], false),
+            (addr2_token_1_only.clone(), vec![&env, 0, amount2_1], false),
+            (
+                addr3_no_claim.clone(),
+                vec![&env, amount3_0, amount3_1],
+                false
+            ),
+        ]
+    );
+
+    // verify claim transfers every token
+    let claim_amounts_1 = dist_client.claim(&addr1);
+    assert_eq!(claim_amounts_1, vec![&env, amount1_0, amount1_1]);
+    assert_eq!(token_0_client.balance(&addr1), amount1_0);
+    assert_eq!(token_1_client.balance(&addr1), amount1_1);
+
+    // claim - validate events
+    let events = env.events().all();
+    let tx_events = vec![&env, events.last().unwrap()];
+    assert_eq!(
+        tx_events,
+        vec![
+            &env,
+            (
+                dist_id.clone(),
+                (Symbol::new(&env, "dist_claim"), addr1.clone()).into_val(&env),
+                (claim_amounts_1, vec![&env, 0_i128, 0]).into_val(&env)
+            )
+        ]
+    );
+
+    let claim_amounts_2 = dist_client.claim(&addr2_token_1_only);
+    assert_eq!(claim_amounts_2, vec![&env, 0, amount2_1]);
+    assert_eq!(token_0_client.balance(&addr2_token_1_only), 0);
+    assert_eq!(token_1_client.balance(&addr2_token_1_only), amount2_1);
+
+    assert_eq!(
+        dist_client.get_status(),
+        DistributionStatus {
+            total_allocated: vec![
+                &env,
+                amount1_0 + amount3_0,
+                amount1_1 + amount2_1 + amount3_1
+            ],
+            total_claimed: vec![&env, amount1_0, amount1_1 + amount2_1],
+            total_fees: vec![&env, 0, 0],
+            total_locked: vec![&env, 0, 0],
+            claim_count: 2,
+        }
+    );
+
+    // verify refund returns every token
+    env.jump(30 * ONE_DAY_LEDGERS + 1);
+
+    let refund_amounts = dist_client.refund();
+    assert_eq!(refund_amounts, vec![&env, amount3_0, amount3_1]);
+    assert_eq!(token_0_client.balance(&admin), amount3_0);
+    assert_eq!(token_1_client.balance(&admin), amount3_1);
+    assert_eq!(token_0_client.balance(&dist_id), 0);
+    assert_eq!(token_1_client.balance(&dist_id), 0);
+}
+
+#[test]
+fn test_native_asset() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let xlm = env.register_native_asset_contract();
+    let xlm_client = TokenClient::new(&env, &xlm);
+    let admin = env.generate_account();
+    env.create_account(&admin, 10_0000000);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+
+    let addr1 = env.generate_account();
+    env.create_account(&addr1, 5_0000000);
+    let amount1: i128 = 100_0000000;
+    let addr2_no_account = env.generate_account();
+    let amount2: i128 = 50_0000000;
+    let addr3_contract = Address::generate(&env);
+    let amount3: i128 = 25_0000000;
+
+    let funder = env.generate_account();
+    env.create_account(&funder, 1000_0000000);
+    xlm_client.transfer(&funder, &dist_id, &(amount1 + amount2 + amount3));
+
+    dist_client.initialize(&vec![&env, xlm.clone()], &deadline, &admin, &vec![&env]);
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (0, amount1)]),
+        (addr2_no_account.clone(), vec![&env, (0, amount2)]),
+        (addr3_contract.clone(), vec![&env, (0, amount3)]),
+    ]);
+    dist_client.finalize(&None, &None);
+
+    dist_client.claim(&addr1);
+    assert_eq!(xlm_client.balance(&addr1), 5_0000000 + amount1);
+
+    dist_client.claim(&addr3_contract);
+    assert_eq!(xlm_client.balance(&addr3_contract), amount3);
+
+    // verify claim fails with a typed error for a classic account that does not exist
+    let result = dist_client.try_claim(&addr2_no_account);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::TransferError as u32
+        )))
+    );
+    assert!(!dist_client.get_claimed(&addr2_no_account));
+
+    // verify the distribution remains claimable once the account exists
+    env.create_account(&addr2_no_account, 1_0000000);
+    dist_client.claim(&addr2_no_account);
+    assert_eq!(xlm_client.balance(&addr2_no_account), 1_0000000 + amount2);
+    assert_eq!(xlm_client.balance(&dist_id), 0);
+
+    // verify refund of native XLM to a classic account admin
+    env.jump(30 * ONE_DAY_LEDGERS + 1);
+    assert_eq!(dist_client.refund(), vec![&env, 0]);
+    assert_eq!(xlm_client.balance(&admin), 10_0000000);
+}
+
+#[test]
+fn test_classic_asset() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let issuer = env.generate_account();
+    env.create_account(&issuer, 10_0000000);
+    let usdc = env.register_classic_asset_contract(&issuer);
+    let usdc_setup_client = StellarAssetClient::new(&env, &usdc);
+    let usdc_client = TokenClient::new(&env, &usdc);
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+
+    let addr1 = env.generate_account();
+    env.create_account(&addr1, 5_0000000);
+    env.create_trustline(&addr1, &issuer, true);
+    let amount1: i128 = 1000_0000000;
+    let addr2_no_trustline = env.generate_account();
+    env.create_account(&addr2_no_trustline, 5_0000000);
+    let amount2: i128 = 2000_0000000;
+    let addr3_unauthorized = env.generate_account();
+    env.create_account(&addr3_unauthorized, 5_0000000);
+    env.create_trustline(&addr3_unauthorized, &issuer, false);
+    let amount3: i128 = 3000_0000000;
+
+    usdc_setup_client.mint(&dist_id, &(amount1 + amount2 + amount3));
+
+    dist_client.initialize(&vec![&env, usdc.clone()], &deadline, &admin, &vec![&env]);
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (0, amount1)]),
+        (addr2_no_trustline.clone(), vec![&env, (0, amount2)]),
+        (addr3_unauthorized.clone(), vec![&env, (0, amount3)]),
+    ]);
+    dist_client.finalize(&None, &None);
+
+    dist_client.claim(&addr1);
+    assert_eq!(usdc_client.balance(&addr1), amount1);
+
+    // verify claim fails with a typed error for an account without a trustline
+    let result = dist_client.try_claim(&addr2_no_trustline);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::TransferError as u32
+        )))
+    );
+
+    // verify claim fails with a typed error for an account that is not authorized
+    let result = dist_client.try_claim(&addr3_unauthorized);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::TransferError as u32
+        )))
+    );
+    assert_eq!(
+        dist_client.get_status(),
+        DistributionStatus {
+            total_allocated: vec![&env, amount1 + amount2 + amount3],
+            total_claimed: vec![&env, amount1],
+            total_fees: vec![&env, 0],
+            total_locked: vec![&env, 0],
+            claim_count: 1,
+        }
+    );
+
+    // verify the distributions remain claimable once the accounts can receive the asset
+    env.create_trustline(&addr2_no_trustline, &issuer, true);
+    dist_client.claim(&addr2_no_trustline);
+    assert_eq!(usdc_client.balance(&addr2_no_trustline), amount2);
+
+    usdc_setup_client.set_authorized(&addr3_unauthorized, &true);
+    dist_client.claim(&addr3_unauthorized);
+    assert_eq!(usdc_client.balance(&addr3_unauthorized), amount3);
+    assert_eq!(usdc_client.balance(&dist_id), 0);
+
+    // verify claim fails with a typed error for a fee recipient without a trustline
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+    let treasury_no_trustline = env.generate_account();
+    env.create_account(&treasury_no_trustline, 5_0000000);
+    usdc_setup_client.mint(&dist_id, &amount1);
+    dist_client.initialize(
+        &vec![&env, usdc.clone()],
+        &deadline,
+        &admin,
+        &vec![
+            &env,
+            DistributorOption::Fee(ClaimFee {
+                rate_bps: 100,
+                recipient: treasury_no_trustline.clone(),
+            }),
+        ],
+    );
+    dist_client.set_distribution(&vec![&env, (addr1.clone(), vec![&env, (0, amount1)])]);
+    dist_client.finalize(&None, &None);
+
+    let result = dist_client.try_claim(&addr1);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::TransferError as u32
+        )))
+    );
+
+    env.create_trustline(&treasury_no_trustline, &issuer, true);
+    dist_client.claim(&addr1);
+    assert_eq!(usdc_client.balance(&treasury_no_trustline), amount1 / 100);
+    assert_eq!(usdc_client.balance(&addr1), 2 * amount1 - amount1 / 100);
+}
+
+#[test]
+fn test_claim_fee() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let token_setup_client = StellarAssetClient::new(&env, &token);
+    let token_client = TokenClient::new(&env, &token);
+    let admin = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+
+    let addr1 = Address::generate(&env);
+    let amount1: i128 = 1_000_000;
+    let addr2 = Address::generate(&env);
+    let amount2: i128 = 12_345;
+
+    token_setup_client.mint(&dist_id, &(amount1 + amount2));
+
+    // verify the fee rate cannot exceed 100%
+    let result = dist_client.try_initialize(
+        &vec![&env, token.clone()],
+        &deadline,
+        &admin,
+        &vec![
+            &env,
+            DistributorOption::Fee(ClaimFee {
+                rate_bps: 10_001,
+                recipient: treasury.clone(),
+            }),
+        ],
+    );
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::FeeError as u32
+        )))
+    );
+
+    let fee = ClaimFee {
+        rate_bps: 250,
+        recipient: treasury.clone(),
+    };
+    dist_client.initialize(
+        &vec![&env, token.clone()],
+        &deadline,
+        &admin,
+        &vec![&env, DistributorOption::Fee(fee.clone())],
+    );
+    assert_eq!(dist_client.get_fee(), Some(fee.clone()));
+
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (0, amount1)]),
+        (addr2.clone(), vec![&env, (0, amount2)]),
+    ]);
+    dist_client.finalize(&None, &None);
+
+    let fee1 = 25_000;
+    let claim_amount_1 = dist_client.claim(&addr1);
+    assert_eq!(claim_amount_1, vec![&env, amount1 - fee1]);
+
+    // claim - validate events
+    let events = env.events().all();
+    let tx_events = vec![&env, events.last().unwrap()];
+    assert_eq!(
+        tx_events,
+        vec![
+            &env,
+            (
+                dist_id.clone(),
+                (Symbol::new(&env, "dist_claim"), addr1.clone()).into_val(&env),
+                (vec![&env, amount1 - fee1], vec![&env, fee1]).into_val(&env)
+            )
+        ]
+    );
+
+    // verify the fee is rounded down
+    let fee2 = 308;
+    let claim_amount_2 = dist_client.claim(&addr2);
+    assert_eq!(claim_amount_2, vec![&env, amount2 - fee2]);
+
+    assert_eq!(token_client.balance(&addr1), amount1 - fee1);
+    assert_eq!(token_client.balance(&addr2), amount2 - fee2);
+    assert_eq!(token_client.balance(&treasury), fee1 + fee2);
+    assert_eq!(token_client.balance(&dist_id), 0);
+    assert_eq!(
+        dist_client.get_status(),
+        DistributionStatus {
+            total_allocated: vec![&env, amount1 + amount2],
+            total_claimed: vec![&env, amount1 + amount2],
+            total_fees: vec![&env, fee1 + fee2],
+            total_locked: vec![&env, 0],
+            claim_count: 2,
+        }
+    );
+
+    // verify a fee that overflows is rejected
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+    let addr3 = Address::generate(&env);
+    dist_client.initialize(
+        &vec![&env, token.clone()],
+        &deadline,
+        &admin,
+        &vec![&env, DistributorOption::Fee(fee.clone())],
+    );
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr3.clone(), vec![&env, (0, i128::MAX / 100)]),
+    ]);
+    dist_client.finalize(&None, &None);
+    let result = dist_client.try_claim(&addr3);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::OverflowError as u32
+        )))
+    );
+}
+
+#[test]
+fn test_claim_to() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+    let receiver_id = env.register_contract(None, mock_receiver::MockReceiver);
+    let receiver_client = mock_receiver::MockReceiverClient::new(&env, &receiver_id);
+    receiver_client.set_distributor(&dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token_0 = env.register_stellar_asset_contract(token_admin.clone());
+    let token_0_setup_client = StellarAssetClient::new(&env, &token_0);
+    let token_0_client = TokenClient::new(&env, &token_0);
+    let token_1 = env.register_stellar_asset_contract(token_admin.clone());
+    let token_1_client = TokenClient::new(&env, &token_1);
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+
+    let addr1 = Address::generate(&env);
+    let amount1: i128 = 7654321;
+
+    token_0_setup_client.mint(&dist_id, &amount1);
+
+    dist_client.initialize(
+        &vec![&env, token_0.clone(), token_1.clone()],
+        &deadline,
+        &admin,
+        &vec![&env],
+    );
+    dist_client.set_distribution(&vec![&env, (addr1.clone(), vec![&env, (0, amount1)])]);
+    dist_client.finalize(&None, &None);
+
+    let claim_amounts = dist_client.claim_to(&addr1, &receiver_id);
+    assert_eq!(claim_amounts, vec![&env, amount1, 0]);
+
+    // claim_to - validate auth
+    assert_eq!(
+        env.auths()[0],
+        (
+            addr1.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    dist_id.clone(),
+                    Symbol::new(&env, "claim_to"),
+                    vec![&env, addr1.to_val(), receiver_id.to_val()]
+                )),
+                sub_invocations: std::vec![]
+            }
+        )
+    );
+
+    // claim_to - validate chain results
+    assert_eq!(token_0_client.balance(&receiver_id), amount1);
+    assert_eq!(token_0_client.balance(&addr1), 0);
+    assert_eq!(token_1_client.balance(&receiver_id), 0);
+    assert_eq!(
+        receiver_client.get_received(),
+        vec![&env, (addr1.clone(), token_0.clone(), amount1)]
+    );
+    assert!(dist_client.get_claimed(&addr1));
+
+    // verify deposits cannot be reported to the receiver by anyone but its distributor
+    let spoofed = Address::generate(&env);
+    let result = receiver_client.try_on_distribution_received(&spoofed, &addr1, &token_0, &1);
+    assert!(result.is_err());
+    env.set_auths(&[]);
+    let result = receiver_client.try_on_distribution_received(&dist_id, &addr1, &token_0, &1);
+    assert!(result.is_err());
+    env.mock_all_auths();
+    assert_eq!(receiver_client.get_received().len(), 1);
+
+    // verify claim_to cannot be re-run
+    let result = dist_client.try_claim_to(&addr1, &receiver_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::AlreadyClaimedError as u32
+        )))
+    );
+
+    // verify locked claims require a lock bonus
+    let result = dist_client.try_claim_locked(&addr1);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::LockError as u32
+        )))
+    );
+}
+
+#[test]
+fn test_claim_locked() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let token_setup_client = StellarAssetClient::new(&env, &token);
+    let token_client = TokenClient::new(&env, &token);
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+    let release_ledger = deadline + 60 * ONE_DAY_LEDGERS;
+
+    let addr1 = Address::generate(&env);
+    let amount1: i128 = 1_000_000;
+    let addr2 = Address::generate(&env);
+    let amount2: i128 = 2_000_001;
+    let addr3 = Address::generate(&env);
+    let amount3: i128 = 3_000_000;
+
+    // verify the lock bonus cannot reduce the distribution
+    let result = dist_client.try_initialize(
+        &vec![&env, token.clone()],
+        &deadline,
+        &admin,
+        &vec![
+            &env,
+            DistributorOption::LockBonus(LockBonus {
+                multiplier_bps: 9_999,
+                release_ledger,
+            }),
+        ],
+    );
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::LockError as u32
+        )))
+    );
+
+    // verify locked tokens cannot be released before the deadline
+    let result = dist_client.try_initialize(
+        &vec![&env, token.clone()],
+        &deadline,
+        &admin,
+        &vec![
+            &env,
+            DistributorOption::LockBonus(LockBonus {
+                multiplier_bps: 15_000,
+                release_ledger: deadline - 1,
+            }),
+        ],
+    );
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::LockError as u32
+        )))
+    );
+
+    let lock_bonus = LockBonus {
+        multiplier_bps: 15_000,
+        release_ledger,
+    };
+    dist_client.initialize(
+        &vec![&env, token.clone()],
+        &deadline,
+        &admin,
+        &vec![&env, DistributorOption::LockBonus(lock_bonus.clone())],
+    );
+    assert_eq!(dist_client.get_lock_bonus(), Some(lock_bonus));
+
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (0, amount1)]),
+        (addr2.clone(), vec![&env, (0, amount2)]),
+        (addr3.clone(), vec![&env, (0, amount3)]),
+    ]);
+    dist_client.finalize(&None, &None);
+
+    // only the base allocations are funded, so the bonus cannot be paid
+    token_setup_client.mint(&dist_id, &(amount1 + amount2 + amount3));
+    let result = dist_client.try_claim_locked(&addr1);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::BalanceError as u32
+        )))
+    );
+
+    // fund the bonus for two users
+    let lock1 = 1_500_000;
+    let lock2 = 3_000_001;
+    token_setup_client.mint(&dist_id, &(lock1 - amount1 + lock2 - amount2));
+
+    let locked_1 = dist_client.claim_locked(&addr1);
+    assert_eq!(locked_1, vec![&env, lock1]);
+
+    // verify the lock is kept past the release ledger
+    let live_until = env.persistent_live_until(&dist_id, &DistributorKey::Lock(addr1.clone()));
+    assert_eq!(live_until, Some(release_ledger + 31 * ONE_DAY_LEDGERS));
+
+    // claim_locked - validate events
+    let events = env.events().all();
+    let tx_events = vec![&env, events.last().unwrap()];
+    assert_eq!(
+        tx_events,
+        vec![
+            &env,
+            (
+                dist_id.clone(),
+                (Symbol::new(&env, "dist_lock"), addr1.clone()).into_val(&env),
+                (vec![&env, lock1], vec![&env, 0_i128]).into_val(&env)
+            )
+        ]
+    );
+
+    // verify the bonus is rounded down
+    let locked_2 = dist_client.claim_locked(&addr2);
+    assert_eq!(locked_2, vec![&env, lock2]);
+    assert_eq!(dist_client.get_lock(&addr2), Some(vec![&env, lock2]));
+
+    // verify a locked claim counts as a claim
+    let result = dist_client.try_claim(&addr1);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::AlreadyClaimedError as u32
+        )))
+    );
+
+    dist_client.claim(&addr3);
+    assert_eq!(token_client.balance(&addr3), amount3);
+    assert_eq!(
+        dist_client.get_status(),
+        DistributionStatus {
+            total_allocated: vec![&env, amount1 + amount2 + amount3],
+            total_claimed: vec![&env, amount1 + amount2 + amount3],
+            total_fees: vec![&env, 0],
+            total_locked: vec![&env, lock1 + lock2],
+            claim_count: 3,
+        }
+    );
+
+    // verify locked tokens cannot be withdrawn before the release ledger
+    let result = dist_client.try_withdraw_lock(&addr1);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::StillLockedError as u32
+        )))
+    );
+
+    // verify refund leaves the locked tokens in the contract
+    env.jump(deadline - env.ledger().sequence() + 1);
+    let refunded = dist_client.refund();
+    assert_eq!(refunded, vec![&env, 0]);
+    assert_eq!(token_client.balance(&dist_id), lock1 + lock2);
+
+    // verify nothing is refunded if the balance drifts below the locked total
+    token_client.burn(&dist_id, &1);
+    let refunded = dist_client.refund();
+    assert_eq!(refunded, vec![&env, 0]);
+    assert_eq!(token_client.balance(&dist_id), lock1 + lock2 - 1);
+    token_setup_client.mint(&dist_id, &1);
+
+    env.jump(release_ledger - env.ledger().sequence());
+    let withdrawn_1 = dist_client.withdraw_lock(&addr1);
+    assert_eq!(withdrawn_1, vec![&env, lock1]);
+    assert_eq!(token_client.balance(&addr1), lock1);
+    assert_eq!(dist_client.get_lock(&addr1), None);
+
+    // withdraw_lock - validate events
+    let events = env.events().all();
+    let tx_events = vec![&env, events.last().unwrap()];
+    assert_eq!(
+        tx_events,
+        vec![
+            &env,
+            (
+                dist_id.clone(),
+                (Symbol::new(&env, "dist_unlock"), addr1.clone()).into_val(&env),
+                vec![&env, lock1].into_val(&env)
+            )
+        ]
+    );
+
+    let result = dist_client.try_withdraw_lock(&addr1);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::NoDistributionError as u32
+        )))
+    );
+
+    dist_client.withdraw_lock(&addr2);
+    assert_eq!(token_client.balance(&addr2), lock2);
+    assert_eq!(token_client.balance(&dist_id), 0);
+    assert_eq!(dist_client.get_status().total_locked, vec![&env, 0]);
+
+    // verify a bonus-multiplied amount that overflows fails with a typed error
+    let dist_client = DistributorClient::new(&env, &register_distributor(&env));
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+    dist_client.initialize(
+        &vec![&env, token.clone()],
+        &deadline,
+        &admin,
+        &vec![
+            &env,
+            DistributorOption::LockBonus(LockBonus {
+                multiplier_bps: u32::MAX,
+                release_ledger: deadline,
+            }),
+        ],
+    );
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (0, i128::MAX / 1000)]),
+    ]);
+    dist_client.finalize(&None, &None);
+    let result = dist_client.try_claim_locked(&addr1);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::OverflowError as u32
+        )))
+    );
+}
+
+#[test]
+fn test_allowlist_registry() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+    let registry_id = env.register_contract(None, mock_registry::MockRegistry);
+    let registry_client = mock_registry::MockRegistryClient::new(&env, &registry_id);
+    let receiver_id = env.register_contract(None, mock_receiver::MockReceiver);
+    mock_receiver::MockReceiverClient::new(&env, &receiver_id).set_distributor(&dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let token_setup_client = StellarAssetClient::new(&env, &token);
+    let token_client = TokenClient::new(&env, &token);
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+
+    let addr1 = Address::generate(&env);
+    let amount1: i128 = 1_000_000;
+    let addr2 = Address::generate(&env);
+    let amount2: i128 = 2_000_000;
+
+    token_setup_client.mint(&dist_id, &(amount1 + amount2));
+
+    dist_client.initialize(
+        &vec![&env, token.clone()],
+        &deadline,
+        &admin,
+        &vec![&env, DistributorOption::Registry(registry_id.clone())],
+    );
+    assert_eq!(dist_client.get_registry(), Some(registry_id.clone()));
+
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (0, amount1)]),
+        (addr2.clone(), vec![&env, (0, amount2)]),
+    ]);
+    dist_client.finalize(&None, &None);
+
+    registry_client.set_allowed(&addr1, &true);
+
+    dist_client.claim(&addr1);
+    assert_eq!(token_client.balance(&addr1), amount1);
+
+    // verify users not allowed by the registry cannot claim
+    let result = dist_client.try_claim(&addr2);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::NotAllowedError as u32
+        )))
+    );
+    let result = dist_client.try_claim_to(&addr2, &receiver_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::NotAllowedError as u32
+        )))
+    );
+    assert!(!dist_client.get_claimed(&addr2));
+
+    // verify users can claim once allowed
+    registry_client.set_allowed(&addr2, &true);
+    dist_client.claim(&addr2);
+    assert_eq!(token_client.balance(&addr2), amount2);
+    assert_eq!(token_client.balance(&dist_id), 0);
+}
+
+#[test]
+fn test_denylist() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+    let receiver_id = env.register_contract(None, mock_receiver::MockReceiver);
+    mock_receiver::MockReceiverClient::new(&env, &receiver_id).set_distributor(&dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let token_setup_client = StellarAssetClient::new(&env, &token);
+    let token_client = TokenClient::new(&env, &token);
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+
+    let addr1 = Address::generate(&env);
+    let amount1: i128 = 1_000_000;
+    let addr2 = Address::generate(&env);
+    let amount2: i128 = 2_000_000;
+    let addr3 = Address::generate(&env);
+
+    token_setup_client.mint(&dist_id, &(amount1 + amount2));
+
+    dist_client.initialize(&vec![&env, token.clone()], &deadline, &admin, &vec![&env]);
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (0, amount1)]),
+        (addr2.clone(), vec![&env, (0, amount2)]),
+    ]);
+
+    dist_client.block(&vec![&env, addr1.clone(), addr3.clone()]);
+    assert_eq!(
+        env.auths()[0],
+        (
+            admin.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    dist_id.clone(),
+                    Symbol::new(&env, "block"),
+                    vec![&env, vec![&env, addr1.clone(), addr3.clone()].to_val()]
+                )),
+                sub_invocations: std::vec![]
+            }
+        )
+    );
+    assert!(dist_client.is_blocked(&addr1));
+    assert!(!dist_client.is_blocked(&addr2));
+
+    // block - validate events
+    let events = env.events().all();
+    let tx_events = vec![&env, events.get_unchecked(events.len() - 2)];
+    assert_eq!(
+        tx_events,
+        vec![
+            &env,
+            (
+                dist_id.clone(),
+                (Symbol::new(&env, "dist_block"), addr1.clone()).into_val(&env),
+                ().into_val(&env)
+            )
+        ]
+    );
+
+    // verify blocked users cannot have their distribution set
+    let result = dist_client.try_set_distribution(&vec![
+        &env,
+        (addr2.clone(), vec![&env, (0, amount2)]),
+        (addr3.clone(), vec![&env, (0, 1)]),
+    ]);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::BlockedError as u32
+        )))
+    );
+
+    dist_client.finalize(&None, &None);
+
+    // verify blocked users cannot claim
+    let result = dist_client.try_claim(&addr1);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::BlockedError as u32
+        )))
+    );
+
+    // verify tokens cannot be claimed to a blocked receiver
+    dist_client.block(&vec![&env, receiver_id.clone()]);
+    let result = dist_client.try_claim_to(&addr2, &receiver_id);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::BlockedError as u32
+        )))
+    );
+
+    dist_client.claim(&addr2);
+    assert_eq!(token_client.balance(&addr2), amount2);
+
+    // verify unblocked users can claim
+    dist_client.unblock(&vec![&env, addr1.clone()]);
+    assert!(!dist_client.is_blocked(&addr1));
+
+    // unblock - validate events
+    let events = env.events().all();
+    let tx_events = vec![&env, events.last().unwrap()];
+    assert_eq!(
+        tx_events,
+        vec![
+            &env,
+            (
+                dist_id.clone(),
+                (Symbol::new(&env, "dist_unblock"), addr1.clone()).into_val(&env),
+                ().into_val(&env)
+            )
+        ]
+    );
+
+    dist_client.claim(&addr1);
+    assert_eq!(token_client.balance(&addr1), amount1);
+    assert_eq!(token_client.balance(&dist_id), 0);
+}
+
+#[test]
+fn test_share_pool() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let token_setup_client = StellarAssetClient::new(&env, &token);
+    let token_client = TokenClient::new(&env, &token);
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+    let timelock = 100;
+    let pool: i128 = 1_000;
+
+    let addr1 = Address::generate(&env);
+    let addr2 = Address::generate(&env);
+    let addr3 = Address::generate(&env);
+    let addr4 = Address::generate(&env);
+
+    token_setup_client.mint(&dist_id, &pool);
+
+    dist_client.initialize(
+        &vec![&env, token.clone()],
+        &deadline,
+        &admin,
+        &vec![&env, DistributorOption::Timelock(timelock)],
+    );
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (0, 1)]),
+        (addr2.clone(), vec![&env, (0, 2)]),
+        (addr3.clone(), vec![&env, (0, 4)]),
+        (addr4.clone(), vec![&env, (0, 2)]),
+    ]);
+
+    // verify the pool must have one non-negative amount per token
+    let result = dist_client.try_finalize(&None, &Some(vec![&env, pool, pool]));
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::InvalidTokenError as u32
+        )))
+    );
+    let result = dist_client.try_finalize(&None, &Some(vec![&env, -1]));
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::NegativeAmountError as u32
+        )))
+    );
+
+    dist_client.finalize(&None, &Some(vec![&env, pool]));
+    assert_eq!(
+        dist_client.get_pool(),
+        Some(SharePool {
+            amounts: vec![&env, pool],
+            total_shares: vec![&env, 9],
+        })
+    );
+
+    // verify corrections and revocations during the timelock update the total shares
+    dist_client.set_distribution(&vec![&env, (addr3.clone(), vec![&env, (0, 3)])]);
+    assert_eq!(dist_client.get_pool().unwrap().total_shares, vec![&env, 8]);
+    dist_client.revoke(&vec![&env, addr4.clone()]);
+    assert_eq!(dist_client.get_pool().unwrap().total_shares, vec![&env, 6]);
+
+    env.jump(timelock);
+
+    // verify revoked shares are not redistributed
+    dist_client.revoke(&vec![&env, addr2.clone()]);
+    assert_eq!(dist_client.get_pool().unwrap().total_shares, vec![&env, 6]);
+
+    // verify claims are rounded down
+    let claim_amount_1 = dist_client.claim(&addr1);
+    assert_eq!(claim_amount_1, vec![&env, 166]);
+    let claim_amount_3 = dist_client.claim(&addr3);
+    assert_eq!(claim_amount_3, vec![&env, 500]);
+    assert_eq!(token_client.balance(&addr1), 166);
+    assert_eq!(token_client.balance(&addr3), 500);
+    assert_eq!(
+        dist_client.get_status(),
+        DistributionStatus {
+            total_allocated: vec![&env, 4],
+            total_claimed: vec![&env, 666],
+            total_fees: vec![&env, 0],
+            total_locked: vec![&env, 0],
+            claim_count: 2,
+        }
+    );
+
+    // verify the revoked pool and dust are refunded
+    env.jump(deadline - env.ledger().sequence() + 1);
+    let refunded = dist_client.refund();
+    assert_eq!(refunded, vec![&env, pool - 666]);
+    assert_eq!(token_client.balance(&admin), pool - 666);
+}
+
+#[test]
+fn test_merkle_claim() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let token_setup_client = StellarAssetClient::new(&env, &token);
+    let token_client = TokenClient::new(&env, &token);
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+
+    let addr1 = Address::generate(&env);
+    let addr2 = Address::generate(&env);
+    let addr3 = Address::generate(&env);
+    let addr4 = Address::generate(&env);
+    let amounts: [i128; 3] = [100, 250, 75];
+
+    // build the tree off-chain
+    let tree = merkle::MerkleTree::new(
+        [&addr1, &addr2, &addr3]
+            .iter()
+            .zip(amounts)
+            .map(|(addr, amount)| (ScAddress::try_from(*addr).unwrap(), std::vec![amount]))
+            .collect(),
+    )
+    .unwrap();
+    assert_eq!(tree.totals(), &[425]);
+
+    // verify a user cannot be allocated twice
+    let user = ScAddress::try_from(&addr2).unwrap();
+    let result = merkle::MerkleTree::new(std::vec![
+        (ScAddress::try_from(&addr1).unwrap(), std::vec![100]),
+        (user.clone(), std::vec![250]),
+        (user.clone(), std::vec![75]),
+    ]);
+    assert_eq!(result.err(), Some(merkle::MerkleError::DuplicateUser(user)));
+    let root = BytesN::from_array(&env, &tree.root());
+    let to_proof = |proof: std::vec::Vec<merkle::Hash>| {
+        let mut result = vec![&env];
+        for hash in proof {
+            result.push_back(BytesN::from_array(&env, &hash));
+        }
+        result
+    };
+
+    token_setup_client.mint(&dist_id, &425);
+    dist_client.initialize(&vec![&env, token.clone()], &deadline, &admin, &vec![&env]);
+    dist_client.set_merkle_root(&root, &vec![&env, 425]);
+    let events = env.events().all();
+    let tx_events = vec![&env, events.last().unwrap()];
+    assert_eq!(
+        tx_events,
+        vec![
+            &env,
+            (
+                dist_id.clone(),
+                (Symbol::new(&env, "dist_merkle"),).into_val(&env),
+                (root.clone(), vec![&env, 425_i128]).into_val(&env),
+            )
+        ]
+    );
+    assert_eq!(dist_client.get_merkle_root().unwrap().root, root);
+    assert_eq!(dist_client.get_status().total_allocated, vec![&env, 425]);
+    dist_client.finalize(&None, &None);
+
+    // verify proofs generated off-chain are accepted
+    let proof1 = tree.proof(0);
+    assert!(merkle::verify_proof(
+        &tree.root(),
+        &merkle::leaf_hash(&ScAddress::try_from(&addr1).unwrap(), &[100]),
+        &proof1
+    ));
+    let claimed = dist_client.claim_merkle(&addr1, &vec![&env, 100], &to_proof(proof1.clone()));
+    assert_eq!(claimed, vec![&env, 100]);
+    let proof3 = tree
+        .proof_for(&ScAddress::try_from(&addr3).unwrap())
+        .unwrap();
+    dist_client.claim_merkle(&addr3, &vec![&env, 75], &to_proof(proof3));
+    assert_eq!(token_client.balance(&addr1), 100);
+    assert_eq!(token_client.balance(&addr3), 75);
+
+    // verify a repeated claim fails
+    let result = dist_client.try_claim_merkle(&addr1, &vec![&env, 100], &to_proof(proof1));
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::AlreadyClaimedError as u32
+        )))
+    );
+
+    // verify wrong amounts, another user's proof, and users outside the tree are rejected
+    let proof2 = to_proof(tree.proof(1));
+    for (user, user_amounts) in [
+        (&addr2, vec![&env, 251]),
+        (&addr2, vec![&env, 250, 0]),
+        (&addr4, vec![&env, 250]),
+    ] {
+        let result = dist_client.try_claim_merkle(user, &user_amounts, &proof2);
+        assert_eq!(
+            result.err(),
+            Some(Ok(Error::from_contract_error(
+                ContractError::MerkleProofError as u32
+            )))
+        );
+    }
+    assert_eq!(token_client.balance(&addr2), 0);
+    dist_client.claim_merkle(&addr2, &vec![&env, 250], &proof2);
+    assert_eq!(token_client.balance(&addr2), 250);
+    assert_eq!(dist_client.get_status().total_claimed, vec![&env, 425]);
+
+    // verify the JSON output matches the tree
+    let json = tree.to_json();
+    assert_eq!(json["totals"][0], "425");
+    assert_eq!(json["claims"][1]["amounts"][0], "250");
+    assert_eq!(
+        json["claims"][1]["address"],
+        std::format!("{}", ScAddress::try_from(&addr2).unwrap())
+    );
+    assert_eq!(
+        json["claims"][1]["proof"].as_array().unwrap().len(),
+        tree.proof(1).len()
+    );
+}
+
+#[test]
+fn test_reconciliation() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let token_setup_client = StellarAssetClient::new(&env, &token);
+    let admin = Address::generate(&env);
+    let treasury = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+    let funded: i128 = 1_000;
+
+    let addr1 = Address::generate(&env);
+    let addr2 = Address::generate(&env);
+    let addr3 = Address::generate(&env);
+    let allocations = [(&addr1, 200_i128), (&addr2, 300), (&addr3, 400)]
+        .iter()
+        .map(|(addr, amount)| (ScAddress::try_from(*addr).unwrap(), std::vec![*amount]))
+        .collect::<std::vec::Vec<_>>();
+
+    token_setup_client.mint(&dist_id, &funded);
+    dist_client.initialize(
+        &vec![&env, token.clone()],
+        &deadline,
+        &admin,
+        &vec![
+            &env,
+            DistributorOption::Fee(ClaimFee {
+                rate_bps: 100,
+                recipient: treasury.clone(),
+            }),
+        ],
+    );
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (0, 200)]),
+        (addr2.clone(), vec![&env, (0, 300)]),
+        (addr3.clone(), vec![&env, (0, 400)]),
+    ]);
+    dist_client.finalize(&None, &None);
+    dist_client.claim(&addr1);
+    dist_client.claim(&addr2);
+    env.jump(deadline - env.ledger().sequence() + 1);
+    dist_client.refund();
+    // a repeated refund has nothing left to return
+    assert_eq!(dist_client.refund(), vec![&env, 0]);
+
+    let mut events = std::vec::Vec::new();
+    for (contract, topics, data) in env.events().all().iter() {
+        if contract != dist_id {
+            continue;
+        }
+        let topics = topics
+            .iter()
+            .map(|topic| ScVal::try_from_val(&env, &topic).unwrap())
+            .collect::<std::vec::Vec<_>>();
+        let data = ScVal::try_from_val(&env, &data).unwrap();
+        if let Some(event) = reconcile::Event::parse(&topics, &data) {
+            events.push(event);
+        }
+    }
+
+    // verify the campaign reconciles, with the refunds summed
+    assert_eq!(events.len(), 4);
+    let report = reconcile::reconcile(&allocations, &[funded], &events);
+    assert!(report.is_balanced(), "{:?}", report.discrepancies);
+    assert_eq!(report.total_allocated, std::vec![900]);
+    assert_eq!(report.total_claimed, std::vec![495]);
+    assert_eq!(report.total_fees, std::vec![5]);
+    assert_eq!(report.total_unclaimed, std::vec![400]);
+    assert_eq!(report.claim_count, 2);
+    assert_eq!(report.refunded, Some(std::vec![500]));
+    assert_eq!(
+        report.users[0],
+        reconcile::UserReport {
+            user: allocations[0].0.clone(),
+            allocated: std::vec![200],
+            claimed: std::vec![198],
+            fees: std::vec![2],
+            unclaimed: std::vec![0],
+        }
+    );
+    assert_eq!(report.users[2].unclaimed, std::vec![400]);
+
+    // verify a double claim is flagged, along with the funds it drained
+    let mut double_claim = events.clone();
+    double_claim.insert(1, events[0].clone());
+    let report = reconcile::reconcile(&allocations, &[funded], &double_claim);
+    assert_eq!(
+        report.discrepancies,
+        std::vec![
+            reconcile::Discrepancy::DoubleClaim {
+                user: allocations[0].0.clone()
+            },
+            reconcile::Discrepancy::RefundMismatch {
+                total_claimed: std::vec![500],
+                claim_count: 2,
+            },
+            reconcile::Discrepancy::RefundMismatch {
+                total_claimed: std::vec![500],
+                claim_count: 2,
+            },
+            reconcile::Discrepancy::MissingFunds {
+                token_index: 0,
+                expected: 300,
+                refunded: 500,
+            },
+        ]
+    );
+
+    // verify a refund that does not account for every claim before it is flagged
+    let mut early_refund = events.clone();
+    let refund = early_refund.remove(2);
+    early_refund.insert(1, refund);
+    let report = reconcile::reconcile(&allocations, &[funded], &early_refund);
+    assert_eq!(
+        report.discrepancies,
+        std::vec![reconcile::Discrepancy::RefundMismatch {
+            total_claimed: std::vec![500],
+            claim_count: 2,
+        }]
+    );
+
+    // verify claims that do not match an allocation are flagged
+    let mut allocations_changed = allocations.clone();
+    allocations_changed[1].1 = std::vec![250];
+    allocations_changed.remove(0);
+    let report = reconcile::reconcile(&allocations_changed, &[funded], &events);
+    assert_eq!(
+        report.discrepancies,
+        std::vec![
+            reconcile::Discrepancy::UnallocatedClaim {
+                user: allocations[0].0.clone()
+            },
+            reconcile::Discrepancy::ClaimMismatch {
+                user: allocations[1].0.clone(),
+                allocated: std::vec![250],
+                claimed: std::vec![300],
+            },
+        ]
+    );
+
+    // verify missing funds and underfunding are flagged
+    let report = reconcile::reconcile(&allocations, &[800], &events);
+    assert_eq!(
+        report.discrepancies,
+        std::vec![
+            reconcile::Discrepancy::Underfunded {
+                token_index: 0,
+                allocated: 900,
+                funded: 800,
+            },
+            reconcile::Discrepancy::MissingFunds {
+                token_index: 0,
+                expected: 300,
+                refunded: 500,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_distributor_fixture() {
+    let env = Env::default();
+    env.set_default_info();
+    env.budget().reset_unlimited();
+
+    let admin = Address::generate(&env);
+    let addr1 = Address::generate(&env);
+    let addr2 = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+    let fixture = create_distributor(
+        &env,
+        &admin,
+        &[(addr1.clone(), 100), (addr2.clone(), 250)],
+        deadline,
+    );
+
+    // verify the distributor is funded, finalized and claimable
+    assert_eq!(fixture.token.balance(&fixture.distributor.address), 350);
+    assert_eq!(fixture.distributor.get_admin(), admin);
+    assert_eq!(fixture.distributor.get_recipient_count(), 2);
+    assert_eq!(fixture.distributor.claim(&addr1), vec![&env, 100]);
+    assert_eq!(fixture.token.balance(&addr1), 100);
+
+    // verify jumping past the deadline allows the unclaimed allocations to be refunded
+    env.jump_to(fixture.deadline + 1);
+    assert_eq!(env.ledger().sequence(), deadline + 1);
+    assert_eq!(fixture.distributor.refund(), vec![&env, 250]);
+    assert_eq!(fixture.token.balance(&fixture.admin), 250);
+}
+
+#[test]
+fn test_temporary_storage_expiry() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+    // entries only outlive the minimum TTL if the contract extends them
+    env.ledger().with_mut(|li| li.min_temp_entry_ttl = 16);
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let token_setup_client = StellarAssetClient::new(&env, &token);
+    let admin = Address::generate(&env);
+    let start = env.ledger().sequence();
+    let deadline = start + 90 * ONE_DAY_LEDGERS;
+
+    let addr1 = Address::generate(&env);
+    let addr2 = Address::generate(&env);
+    let addr3 = Address::generate(&env);
+
+    token_setup_client.mint(&dist_id, &300);
+    dist_client.initialize(&vec![&env, token.clone()], &deadline, &admin, &vec![&env]);
+    dist_client.set_distribution(&vec![
+        &env,
+        (addr1.clone(), vec![&env, (0, 100)]),
+        (addr2.clone(), vec![&env, (0, 100)]),
+        (addr3.clone(), vec![&env, (0, 100)]),
+    ]);
+    dist_client.finalize(&None, &None);
+
+    // verify allocations live until a day past the deadline
+    for user in [&addr1, &addr2, &addr3] {
+        let live_until = env.temporary_live_until(&dist_id, &DistributorKey::Dist(user.clone()));
+        assert_eq!(live_until, Some(deadline + ONE_DAY_LEDGERS));
+    }
+    let live_until = env.temporary_live_until(&dist_id, &DistributorKey::Recipients(0));
+    assert_eq!(live_until, Some(deadline + ONE_DAY_LEDGERS));
+
+    // verify the claim flag outlives the deadline
+    env.jump(1);
+    dist_client.claim(&addr1);
+    let live_until = env.temporary_live_until(&dist_id, &DistributorKey::Claim(addr1.clone()));
+    assert_eq!(live_until, Some(deadline + ONE_DAY_LEDGERS));
+
+    // verify allocations are still claimable at the deadline, and claims cannot repeat
+    env.jump_to(deadline);
+    env.expire_temporary_entries();
+    assert!(dist_client.get_claimed(&addr1));
+    assert_eq!(dist_client.claim(&addr2), vec![&env, 100]);
+    let result = dist_client.try_claim(&addr1);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::AlreadyClaimedError as u32
+        )))
+    );
+
+    // verify expired allocations and claim flags after the deadline never allow a claim
+    env.jump_to(deadline + 2 * ONE_DAY_LEDGERS);
+    env.expire_temporary_entries();
+    let live_until = env.temporary_live_until(&dist_id, &DistributorKey::Dist(addr3.clone()));
+    assert_eq!(live_until, None);
+    assert!(!dist_client.get_claimed(&addr1));
+    for user in [&addr1, &addr3] {
+        let result = dist_client.try_claim(user);
+        assert_eq!(
+            result.err(),
+            Some(Ok(Error::from_contract_error(
+                ContractError::DeadlineError as u32
+            )))
+        );
+    }
+    assert_eq!(dist_client.refund(), vec![&env, 100]);
+}
+
+#[test]
+fn test_temporary_ttl() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 90 * ONE_DAY_LEDGERS;
+
+    let addr1 = Address::generate(&env);
+    let addr2 = Address::generate(&env);
+
+    dist_client.initialize(&vec![&env, token.clone()], &deadline, &admin, &vec![&env]);
+
+    // verify entries uploaded later still live until a day past the deadline
+    env.jump(30 * ONE_DAY_LEDGERS);
+    dist_client.set_distribution(&vec![&env, (addr1.clone(), vec![&env, (0, 100)])]);
+    let live_until = env.temporary_live_until(&dist_id, &DistributorKey::Dist(addr1.clone()));
+    assert_eq!(live_until, Some(deadline + ONE_DAY_LEDGERS));
+    let live_until = env.temporary_live_until(&dist_id, &DistributorKey::Recipients(0));
+    assert_eq!(live_until, Some(deadline + ONE_DAY_LEDGERS));
+
+    // verify uploads are rejected if the network cannot keep the entries until the deadline,
+    // including when the TTL needed is exactly the max entry TTL
+    for max_entry_ttl in [60 * ONE_DAY_LEDGERS, 61 * ONE_DAY_LEDGERS] {
+        env.ledger().with_mut(|li| li.max_entry_ttl = max_entry_ttl);
+        let result =
+            dist_client.try_set_distribution(&vec![&env, (addr2.clone(), vec![&env, (0, 100)])]);
+        assert_eq!(
+            result.err(),
+            Some(Ok(Error::from_contract_error(
+                ContractError::TtlError as u32
+            )))
+        );
+        assert_eq!(dist_client.get_recipient_count(), 1);
+    }
+
+    // verify uploads succeed once the network can keep the entries until the deadline
+    env.ledger()
+        .with_mut(|li| li.max_entry_ttl = 61 * ONE_DAY_LEDGERS + 1);
+    dist_client.set_distribution(&vec![&env, (addr2.clone(), vec![&env, (0, 100)])]);
+    let live_until = env.temporary_live_until(&dist_id, &DistributorKey::Dist(addr2.clone()));
+    assert_eq!(live_until, Some(deadline + ONE_DAY_LEDGERS));
+    assert_eq!(dist_client.get_recipient_count(), 2);
+}
+
+#[test]
+#[cfg(feature = "wasm-tests")]
+fn test_upgrade() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+
+    dist_client.initialize(&vec![&env, token.clone()], &deadline, &admin, &vec![&env]);
+    assert!(!dist_client.get_upgrade_renounced());
+
+    let new_wasm_hash = env.deployer().upload_contract_wasm(upgrade_wasm::WASM);
+    dist_client.upgrade(&new_wasm_hash);
+    assert_eq!(
+        env.auths()[0],
+        (
+            admin.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    dist_id.clone(),
+                    Symbol::new(&env, "upgrade"),
+                    vec![&env, new_wasm_hash.to_val()]
+                )),
+                sub_invocations: std::vec![]
+            }
+        )
+    );
+
+    // upgrade - validate events
+    let events = env.events().all();
+    let tx_events = vec![&env, events.last().unwrap()];
+    assert_eq!(
+        tx_events,
+        vec![
+            &env,
+            (
+                dist_id.clone(),
+                (Symbol::new(&env, "dist_upgrade"),).into_val(&env),
+                new_wasm_hash.into_val(&env)
+            )
+        ]
+    );
+
+    // verify the contract runs the new wasm with the existing storage
+    let upgraded_client = upgrade_wasm::Client::new(&env, &dist_id);
+    assert_eq!(upgraded_client.version(), 2);
+    assert_eq!(upgraded_client.get_admin(), admin);
+    assert_eq!(upgraded_client.get_deadline(), deadline);
+
+    // verify the upgrade can be renounced
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+    dist_client.initialize(&vec![&env, token.clone()], &deadline, &admin, &vec![&env]);
+
+    dist_client.renounce_upgrade();
+    assert_eq!(
+        env.auths()[0],
+        (
+            admin.clone(),
+            AuthorizedInvocation {
+                function: AuthorizedFunction::Contract((
+                    dist_id.clone(),
+                    Symbol::new(&env, "renounce_upgrade"),
+                    vec![&env]
+                )),
+                sub_invocations: std::vec![]
+            }
+        )
+    );
+    assert!(dist_client.get_upgrade_renounced());
+
+    let events = env.events().all();
+    let tx_events = vec![&env, events.last().unwrap()];
+    assert_eq!(
+        tx_events,
+        vec![
+            &env,
+            (
+                dist_id.clone(),
+                (Symbol::new(&env, "dist_renounce"),).into_val(&env),
+                ().into_val(&env)
+            )
+        ]
+    );
+
+    let result = dist_client.try_upgrade(&new_wasm_hash);
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::UpgradeError as u32
+        )))
+    );
+}
+
+#[test]
+fn test_migrate() {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+
+    let token_admin = Address::generate(&env);
+    let token = env.register_stellar_asset_contract(token_admin.clone());
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + 30 * ONE_DAY_LEDGERS;
+
+    // verify an uninitialized contract has no layout to migrate
+    assert_eq!(dist_client.get_schema_version(), 0);
+    let result = dist_client.try_migrate();
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::MigrationError as u32
+        )))
+    );
+
+    // verify initialize writes the current layout, which cannot be migrated
+    dist_client.initialize(&vec![&env, token.clone()], &deadline, &admin, &vec![&env]);
+    assert_eq!(SCHEMA_VERSION, 1);
+    assert_eq!(dist_client.get_schema_version(), SCHEMA_VERSION);
+    let result = dist_client.try_migrate();
+    assert_eq!(
+        result.err(),
+        Some(Ok(Error::from_contract_error(
+            ContractError::MigrationError as u32
+        )))
+    );
+    assert_eq!(dist_client.get_schema_version(), SCHEMA_VERSION);
+    assert_eq!(dist_client.get_admin(), admin);
 }
@@ -0,0 +1,155 @@
+//! Randomized tests over allocation sets, overwrites, claim orders and ledger jumps, checking
+//! the distributor's invariants against a model of the campaign.
+
+use std::{format, vec::Vec};
+
+use crate::{
+    errors::ContractError, storage::ONE_DAY_LEDGERS, test::register_distributor,
+    testutils::EnvTestUtils, DistributorClient,
+};
+use proptest::{collection::vec as prop_vec, prelude::*};
+use soroban_sdk::{
+    testutils::Address as _,
+    token::{StellarAssetClient, TokenClient},
+    vec, Address, Env, Error,
+};
+
+const MAX_USERS: usize = 6;
+const MAX_AMOUNT: i128 = 1_000_000;
+const DEADLINE_LEDGERS: u32 = 30 * ONE_DAY_LEDGERS;
+// keep the timeline well within the TTL of the contract instance
+const MAX_LEDGERS_PAST_DEADLINE: u32 = 10 * ONE_DAY_LEDGERS;
+
+#[derive(Clone, Debug)]
+enum Action {
+    /// Claim for the user at the index
+    Claim(usize),
+    /// Jump the ledger forward
+    Jump(u32),
+}
+
+fn action() -> impl Strategy<Value = Action> {
+    prop_oneof![
+        2 => (0..MAX_USERS).prop_map(Action::Claim),
+        1 => (0..10 * ONE_DAY_LEDGERS).prop_map(Action::Jump),
+    ]
+}
+
+fn contract_error(error: ContractError) -> Error {
+    Error::from_contract_error(error as u32)
+}
+
+/// Run a campaign and check its invariants
+///
+/// ### Arguments
+/// * `allocations` - The allocations to upload in two batches, as `(user index, amount)`.
+///   Later allocations of the same user overwrite earlier ones.
+/// * `surplus` - The amount funded above the total allocated
+/// * `actions` - The claims and ledger jumps to run after finalizing, in order
+fn run_campaign(
+    allocations: &[(usize, i128)],
+    surplus: i128,
+    actions: &[Action],
+) -> Result<(), TestCaseError> {
+    let env = Env::default();
+    env.set_default_info();
+    env.mock_all_auths();
+    env.budget().reset_unlimited();
+
+    let dist_id = register_distributor(&env);
+    let dist_client = DistributorClient::new(&env, &dist_id);
+    let token = env.register_stellar_asset_contract(Address::generate(&env));
+    let token_client = TokenClient::new(&env, &token);
+    let admin = Address::generate(&env);
+    let deadline = env.ledger().sequence() + DEADLINE_LEDGERS;
+    let users: Vec<Address> = (0..MAX_USERS).map(|_| Address::generate(&env)).collect();
+
+    // the model of each user's allocation and whether they have been paid
+    let mut allocated = [0_i128; MAX_USERS];
+    for (index, amount) in allocations {
+        allocated[*index] = *amount;
+    }
+    let mut paid = [false; MAX_USERS];
+    let funded = allocated.iter().sum::<i128>() + surplus;
+
+    StellarAssetClient::new(&env, &token).mint(&dist_id, &funded);
+    dist_client.initialize(&vec![&env, token.clone()], &deadline, &admin, &vec![&env]);
+    let (first, second) = allocations.split_at(allocations.len() / 2);
+    for batch in [first, second] {
+        let mut distributions = vec![&env];
+        for (index, amount) in batch {
+            distributions.push_back((users[*index].clone(), vec![&env, (0_u32, *amount)]));
+        }
+        dist_client.set_distribution(&distributions);
+    }
+    dist_client.finalize(&None, &None);
+
+    for action in actions {
+        match action {
+            Action::Jump(ledgers) => {
+                let end = deadline + MAX_LEDGERS_PAST_DEADLINE;
+                env.jump((*ledgers).min(end - env.ledger().sequence()));
+            }
+            Action::Claim(index) => {
+                let user = &users[*index];
+                let result = dist_client.try_claim(user);
+                if env.ledger().sequence() > deadline {
+                    // post-deadline claims always fail
+                    prop_assert!(result.is_err());
+                } else if paid[*index] {
+                    prop_assert_eq!(
+                        result.err(),
+                        Some(Ok(contract_error(ContractError::AlreadyClaimedError)))
+                    );
+                } else if allocated[*index] == 0 {
+                    prop_assert_eq!(
+                        result.err(),
+                        Some(Ok(contract_error(ContractError::NoDistributionError)))
+                    );
+                } else {
+                    prop_assert_eq!(result, Ok(Ok(vec![&env, allocated[*index]])));
+                    paid[*index] = true;
+                }
+            }
+        }
+
+        // each user is paid at most once, and transfers never exceed the funding
+        let mut total_paid = 0;
+        for (index, user) in users.iter().enumerate() {
+            let expected = if paid[index] { allocated[index] } else { 0 };
+            prop_assert_eq!(token_client.balance(user), expected);
+            total_paid += expected;
+        }
+        prop_assert!(total_paid <= funded);
+        prop_assert_eq!(token_client.balance(&dist_id), funded - total_paid);
+    }
+
+    // refund returns exactly the balance left after claims
+    let total_paid: i128 = (0..MAX_USERS)
+        .filter(|index| paid[*index])
+        .map(|index| allocated[index])
+        .sum();
+    if env.ledger().sequence() <= deadline {
+        env.jump_to(deadline + 1);
+    }
+    prop_assert_eq!(dist_client.refund(), vec![&env, funded - total_paid]);
+    prop_assert_eq!(token_client.balance(&admin), funded - total_paid);
+    prop_assert_eq!(token_client.balance(&dist_id), 0);
+    for user in &users {
+        prop_assert!(dist_client.try_claim(user).is_err());
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(24))]
+
+    #[test]
+    fn test_campaign_invariants(
+        allocations in prop_vec((0..MAX_USERS, 0..MAX_AMOUNT), 1..12),
+        surplus in 0..MAX_AMOUNT,
+        actions in prop_vec(action(), 0..24),
+    ) {
+        run_campaign(&allocations, surplus, &actions)?;
+    }
+}
@@ -1,20 +1,117 @@
-#![cfg(test)]
+#![cfg(any(test, feature = "testutils"))]
 
-use crate::storage::ONE_DAY_LEDGERS;
+use std::rc::Rc;
+
+use crate::{storage::ONE_DAY_LEDGERS, Distributor, DistributorClient};
 use soroban_sdk::{
-    testutils::{Ledger as _, LedgerInfo},
-    Env,
+    testutils::{Address as _, Ledger as _, LedgerInfo},
+    token::{StellarAssetClient, TokenClient},
+    vec,
+    xdr::{
+        AccountEntry, AccountEntryExt, AccountId, AlphaNum4, Asset, AssetCode4,
+        ContractDataDurability, ContractExecutable, ContractIdPreimage, CreateContractArgs,
+        HostFunction, LedgerEntry, LedgerEntryData, LedgerEntryExt, LedgerKey, LedgerKeyAccount,
+        LedgerKeyContractData, LedgerKeyTrustLine, PublicKey, ScAddress, ScVal, SequenceNumber,
+        Thresholds, TrustLineAsset, TrustLineEntry, TrustLineEntryExt, TrustLineFlags, Uint256,
+    },
+    Address, Env, IntoVal, TryFromVal, Val, Vec,
 };
 
+/// A finalized distributor funded with a Stellar Asset Contract token
+pub struct DistributorFixture<'a> {
+    pub distributor: DistributorClient<'a>,
+    pub token: TokenClient<'a>,
+    pub token_admin: StellarAssetClient<'a>,
+    pub admin: Address,
+    pub deadline: u32,
+}
+
+/// Register the distributor and a Stellar Asset Contract token, fund the distributor with the
+/// sum of the allocations, upload the allocations, and finalize with no timelock, so the
+/// allocations can be claimed immediately. Mocks all auths on the env.
+///
+/// ### Arguments
+/// * `admin` - The admin of the distributor
+/// * `allocations` - The amount of the token allocated to each user
+/// * `deadline` - The ledger sequence after which claims are no longer accepted
+pub fn create_distributor<'a>(
+    e: &Env,
+    admin: &Address,
+    allocations: &[(Address, i128)],
+    deadline: u32,
+) -> DistributorFixture<'a> {
+    e.mock_all_auths();
+
+    let distributor = DistributorClient::new(e, &e.register_contract(None, Distributor));
+    let token_id = e.register_stellar_asset_contract(Address::generate(e));
+    let token = TokenClient::new(e, &token_id);
+    let token_admin = StellarAssetClient::new(e, &token_id);
+
+    let total: i128 = allocations.iter().map(|(_, amount)| amount).sum();
+    token_admin.mint(&distributor.address, &total);
+    distributor.initialize(&vec![e, token_id], &deadline, admin, &vec![e]);
+    let mut distributions = Vec::new(e);
+    for (user, amount) in allocations {
+        distributions.push_back((user.clone(), vec![e, (0_u32, *amount)]));
+    }
+    distributor.set_distribution(&distributions);
+    distributor.finalize(&None, &None);
+
+    DistributorFixture {
+        distributor,
+        token,
+        token_admin,
+        admin: admin.clone(),
+        deadline,
+    }
+}
+
 pub trait EnvTestUtils {
     /// Jump the env by the given amount of ledgers. Assumes 5 seconds per ledger.
     fn jump(&self, ledgers: u32);
 
+    /// Jump the env forward to the given ledger sequence. Assumes 5 seconds per ledger.
+    fn jump_to(&self, sequence: u32);
+
     /// Set the ledger to the default LedgerInfo
     ///
     /// Time -> 1441065600 (Sept 1st, 2015 12:00:00 AM UTC)
     /// Sequence -> 100
     fn set_default_info(&self);
+
+    /// Generate the address of a classic Stellar account that does not exist yet
+    fn generate_account(&self) -> Address;
+
+    /// Create the classic Stellar account `account` holding `balance` stroops of XLM
+    fn create_account(&self, account: &Address, balance: i64);
+
+    /// Create a trustline for a classic account to the "USDC" asset issued by `issuer`
+    fn create_trustline(&self, account: &Address, issuer: &Address, authorized: bool);
+
+    /// Register the Stellar Asset Contract for native XLM
+    fn register_native_asset_contract(&self) -> Address;
+
+    /// Register the Stellar Asset Contract for the "USDC" asset issued by the classic account
+    /// `issuer`. The issuer is the admin of the contract.
+    fn register_classic_asset_contract(&self, issuer: &Address) -> Address;
+
+    /// Get the ledger sequence a temporary entry of `contract` lives until, if it exists
+    fn temporary_live_until<K: IntoVal<Env, Val>>(
+        &self,
+        contract: &Address,
+        key: &K,
+    ) -> Option<u32>;
+
+    /// Get the ledger sequence a persistent entry of `contract` lives until, if it exists
+    fn persistent_live_until<K: IntoVal<Env, Val>>(
+        &self,
+        contract: &Address,
+        key: &K,
+    ) -> Option<u32>;
+
+    /// Delete every temporary entry whose TTL has elapsed, as the network does. The test env
+    /// otherwise keeps expired entries readable.
+    fn expire_temporary_entries(&self);
 }
 
 impl EnvTestUtils for Env {
@@ -25,12 +122,16 @@ impl EnvTestUtils for Env {
             sequence_number: self.ledger().sequence().saturating_add(ledgers),
             network_id: Default::default(),
             base_reserve: 10,
-            min_temp_entry_ttl: 1 * ONE_DAY_LEDGERS,
+            min_temp_entry_ttl: ONE_DAY_LEDGERS,
             min_persistent_entry_ttl: 120 * ONE_DAY_LEDGERS,
             max_entry_ttl: 365 * ONE_DAY_LEDGERS,
         });
     }
 
+    fn jump_to(&self, sequence: u32) {
+        self.jump(sequence.saturating_sub(self.ledger().sequence()));
+    }
+
     fn set_default_info(&self) {
         self.ledger().set(LedgerInfo {
             timestamp: 1441065600, // Sept 1st, 2015 12:00:00 AM UTC
@@ -38,9 +139,165 @@ impl EnvTestUtils for Env {
             sequence_number: 100,
             network_id: Default::default(),
             base_reserve: 10,
-            min_temp_entry_ttl: 1 * ONE_DAY_LEDGERS,
+            min_temp_entry_ttl: ONE_DAY_LEDGERS,
             min_persistent_entry_ttl: 120 * ONE_DAY_LEDGERS,
             max_entry_ttl: 365 * ONE_DAY_LEDGERS,
         });
     }
+
+    fn generate_account(&self) -> Address {
+        // re-use a generated contract id as the account's public key
+        let account_id = match ScAddress::try_from(&Address::generate(self)).unwrap() {
+            ScAddress::Contract(hash) => {
+                AccountId(PublicKey::PublicKeyTypeEd25519(Uint256(hash.0)))
+            }
+            ScAddress::Account(account_id) => account_id,
+        };
+        Address::try_from_val(self, &ScAddress::Account(account_id)).unwrap()
+    }
+
+    fn create_account(&self, account: &Address, balance: i64) {
+        let account_id = to_account_id(account);
+        let key = LedgerKey::Account(LedgerKeyAccount {
+            account_id: account_id.clone(),
+        });
+        let entry = LedgerEntryData::Account(AccountEntry {
+            account_id: account_id.clone(),
+            balance,
+            flags: 0,
+            home_domain: Default::default(),
+            inflation_dest: None,
+            num_sub_entries: 0,
+            seq_num: SequenceNumber(0),
+            thresholds: Thresholds([1; 4]),
+            signers: Default::default(),
+            ext: AccountEntryExt::V0,
+        });
+        put_ledger_entry(self, key, entry);
+    }
+
+    fn create_trustline(&self, account: &Address, issuer: &Address, authorized: bool) {
+        let account_id = to_account_id(account);
+        let asset = TrustLineAsset::CreditAlphanum4(usdc(issuer));
+        let key = LedgerKey::Trustline(LedgerKeyTrustLine {
+            account_id: account_id.clone(),
+            asset: asset.clone(),
+        });
+        let entry = LedgerEntryData::Trustline(TrustLineEntry {
+            account_id,
+            asset,
+            balance: 0,
+            limit: i64::MAX,
+            flags: if authorized {
+                TrustLineFlags::AuthorizedFlag as u32
+            } else {
+                0
+            },
+            ext: TrustLineEntryExt::V0,
+        });
+        put_ledger_entry(self, key, entry);
+    }
+
+    fn register_native_asset_contract(&self) -> Address {
+        register_asset_contract(self, Asset::Native)
+    }
+
+    fn register_classic_asset_contract(&self, issuer: &Address) -> Address {
+        register_asset_contract(self, Asset::CreditAlphanum4(usdc(issuer)))
+    }
+
+    fn temporary_live_until<K: IntoVal<Env, Val>>(
+        &self,
+        contract: &Address,
+        key: &K,
+    ) -> Option<u32> {
+        live_until(self, contract, key, ContractDataDurability::Temporary)
+    }
+
+    fn persistent_live_until<K: IntoVal<Env, Val>>(
+        &self,
+        contract: &Address,
+        key: &K,
+    ) -> Option<u32> {
+        live_until(self, contract, key, ContractDataDurability::Persistent)
+    }
+
+    fn expire_temporary_entries(&self) {
+        let sequence = self.ledger().sequence();
+        let budget = self.host().budget_cloned();
+        self.host()
+            .with_mut_storage(|storage| {
+                let expired: std::vec::Vec<Rc<LedgerKey>> = storage
+                    .map
+                    .iter(&budget)?
+                    .filter(|(key, entry)| {
+                        matches!(key.as_ref(), LedgerKey::ContractData(data)
+                            if data.durability == ContractDataDurability::Temporary)
+                            && matches!(entry, Some((_, Some(live_until))) if *live_until < sequence)
+                    })
+                    .map(|(key, _)| key.clone())
+                    .collect();
+                for key in expired {
+                    storage.map = storage.map.insert(key, None, &budget)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+    }
+}
+
+fn live_until<K: IntoVal<Env, Val>>(
+    e: &Env,
+    contract: &Address,
+    key: &K,
+    durability: ContractDataDurability,
+) -> Option<u32> {
+    let key = Rc::new(LedgerKey::ContractData(LedgerKeyContractData {
+        contract: ScAddress::try_from(contract).unwrap(),
+        key: ScVal::try_from_val(e, &key.into_val(e)).unwrap(),
+        durability,
+    }));
+    let budget = e.host().budget_cloned();
+    e.host()
+        .with_mut_storage(
+            |storage| match storage.map.get::<Rc<LedgerKey>>(&key, &budget)? {
+                Some(Some((_, live_until))) => Ok(*live_until),
+                _ => Ok(None),
+            },
+        )
+        .unwrap()
+}
+
+fn to_account_id(account: &Address) -> AccountId {
+    match ScAddress::try_from(account).unwrap() {
+        ScAddress::Account(account_id) => account_id,
+        ScAddress::Contract(_) => panic!("address is not a classic account"),
+    }
+}
+
+fn usdc(issuer: &Address) -> AlphaNum4 {
+    AlphaNum4 {
+        asset_code: AssetCode4(*b"USDC"),
+        issuer: to_account_id(issuer),
+    }
+}
+
+fn put_ledger_entry(e: &Env, key: LedgerKey, data: LedgerEntryData) {
+    let entry = LedgerEntry {
+        last_modified_ledger_seq: 0,
+        data,
+        ext: LedgerEntryExt::V0,
+    };
+    let budget = e.host().budget_cloned();
+    e.host()
+        .with_mut_storage(|storage| storage.put(&Rc::new(key), &Rc::new(entry), None, &budget))
+        .unwrap();
+}
+
+fn register_asset_contract(e: &Env, asset: Asset) -> Address {
+    let create = HostFunction::CreateContract(CreateContractArgs {
+        contract_id_preimage: ContractIdPreimage::Asset(asset),
+        executable: ContractExecutable::StellarAsset,
+    });
+    Address::try_from_val(e, &e.host().invoke_function(create).unwrap()).unwrap()
 }
@@ -0,0 +1,74 @@
+use soroban_sdk::{contracttype, Address, BytesN, Vec};
+
+/// The aggregate progress of the distribution
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct DistributionStatus {
+    /// The sum of all unclaimed and claimed distributions of each token, or of all shares of each
+    /// token if a share pool is set
+    pub total_allocated: Vec<i128>,
+    /// The sum of all claimed distributions of each token, including fees
+    pub total_claimed: Vec<i128>,
+    /// The sum of all fees taken from claimed distributions of each token
+    pub total_fees: Vec<i128>,
+    /// The sum of all tokens currently locked for users of each token, including lock bonuses
+    pub total_locked: Vec<i128>,
+    /// The number of users that have claimed their distribution
+    pub claim_count: u32,
+}
+
+/// An optional feature of a distribution, enabled when it is initialized
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub enum DistributorOption {
+    /// The number of ledgers after `finalize` before claims open, during which the admin can
+    /// still correct distributions. Defaults to 0.
+    Timelock(u32),
+    /// The fee taken from each claim and sent to a treasury
+    Fee(ClaimFee),
+    /// The bonus for locking a distribution via `claim_locked`
+    LockBonus(LockBonus),
+    /// The allowlist registry that must allow users to claim
+    Registry(Address),
+}
+
+/// The fee taken from each claim and sent to a treasury
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct ClaimFee {
+    /// The fee rate, in basis points of the claimed amount
+    pub rate_bps: u32,
+    /// The recipient of the fee
+    pub recipient: Address,
+}
+
+/// The bonus for users who lock their distribution in the contract instead of claiming it
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct LockBonus {
+    /// The multiplier applied to locked distributions, in basis points (e.g. 15000 for 1.5x)
+    pub multiplier_bps: u32,
+    /// The ledger sequence locked tokens can be withdrawn at, no earlier than the deadline
+    pub release_ledger: u32,
+}
+
+/// The pool of each token split between users by their shares, fixed when the distribution is
+/// finalized
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct SharePool {
+    /// The amount of each token split between users
+    pub amounts: Vec<i128>,
+    /// The total shares of each token the pool is split by
+    pub total_shares: Vec<i128>,
+}
+
+/// The root of a Merkle tree of allocations, claimable with a proof instead of being stored
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[contracttype]
+pub struct MerkleRoot {
+    /// The root of the Merkle tree
+    pub root: BytesN<32>,
+    /// The sum of all allocations in the tree of each token
+    pub totals: Vec<i128>,
+}
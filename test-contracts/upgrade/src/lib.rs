@@ -0,0 +1,40 @@
+#![no_std]
+
+use soroban_sdk::{contract, contractimpl, contracttype, unwrap::UnwrapOptimized, Address, Env};
+
+/// The subset of the distributor's instance storage keys read by this contract
+#[derive(Clone)]
+#[contracttype]
+pub enum InstanceKey {
+    Admin,
+    Deadline,
+}
+
+/// A stand-in for a future version of the distributor, used to test upgrades. It reads the
+/// distributor's instance storage to verify storage is kept across an upgrade.
+#[contract]
+pub struct UpgradedDistributor;
+
+#[contractimpl]
+impl UpgradedDistributor {
+    /// Fetch the version of the contract
+    pub fn version(_e: Env) -> u32 {
+        2
+    }
+
+    /// Fetch the admin set by the distributor
+    pub fn get_admin(e: Env) -> Address {
+        e.storage()
+            .instance()
+            .get(&InstanceKey::Admin)
+            .unwrap_optimized()
+    }
+
+    /// Fetch the deadline set by the distributor
+    pub fn get_deadline(e: Env) -> u32 {
+        e.storage()
+            .instance()
+            .get(&InstanceKey::Deadline)
+            .unwrap_optimized()
+    }
+}